@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE user_raids DROP COLUMN took_gold;
+ALTER TABLE characters DROP COLUMN gold_earner;
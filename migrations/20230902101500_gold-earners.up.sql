@@ -0,0 +1,18 @@
+-- Add up migration script here
+ALTER TABLE characters ADD gold_earner BOOLEAN NOT NULL DEFAULT 1;
+
+ALTER TABLE user_raids ADD took_gold BOOLEAN NOT NULL DEFAULT 1;
+
+-- The six-character rule: only the six highest characters of a roster earn gold by default
+UPDATE characters c
+JOIN (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY item_level DESC, id) AS pos
+    FROM characters
+) ranked
+ON ranked.id = c.id
+SET c.gold_earner = ranked.pos <= 6;
+
+UPDATE user_raids ur
+JOIN characters c
+ON c.id = ur.character_id
+SET ur.took_gold = c.gold_earner;
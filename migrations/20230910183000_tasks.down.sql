@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE resets;
+DROP TABLE character_tasks;
+DROP TABLE tasks;
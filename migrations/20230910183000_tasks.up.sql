@@ -0,0 +1,33 @@
+-- Add up migration script here
+CREATE TABLE tasks (
+    id INTEGER PRIMARY KEY AUTO_INCREMENT,
+    name VARCHAR(255) NOT NULL,
+    reset ENUM('daily', 'weekly') NOT NULL,
+    rest_gain INTEGER NOT NULL DEFAULT 0,
+    rest_cost INTEGER NOT NULL DEFAULT 0,
+    rest_max INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE character_tasks (
+    character_id INTEGER NOT NULL,
+    task_id INTEGER NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT 0,
+    rest_bonus INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (character_id, task_id),
+    FOREIGN KEY (character_id) REFERENCES characters(id) ON DELETE CASCADE,
+    FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+CREATE TABLE resets (
+    kind ENUM('daily', 'weekly') PRIMARY KEY,
+    last_reset TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO resets (kind) VALUES ("daily"), ("weekly");
+
+INSERT INTO tasks (name, reset, rest_gain, rest_cost, rest_max) VALUES
+    ("Chaos Dungeon", "daily", 20, 40, 100),
+    ("Guardian Raid", "daily", 10, 20, 100),
+    ("Una's Tasks", "daily", 10, 20, 100),
+    ("Weekly Una's Tasks", "weekly", 0, 0, 0),
+    ("Abyss Dungeon", "weekly", 0, 0, 0);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE raids DROP COLUMN gold;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+ALTER TABLE raids ADD gold INTEGER NOT NULL DEFAULT 0;
+
+UPDATE raids SET gold = 1600 WHERE name = "Argos";
+UPDATE raids SET gold = 1200 WHERE name = "Valtan" AND difficulty = "Normal";
+UPDATE raids SET gold = 1800 WHERE name = "Valtan" AND difficulty = "Hard";
+UPDATE raids SET gold = 1600 WHERE name = "Vykas" AND difficulty = "Normal";
+UPDATE raids SET gold = 2400 WHERE name = "Vykas" AND difficulty = "Hard";
+UPDATE raids SET gold = 3000 WHERE name = "Kakul-Saydon";
+UPDATE raids SET gold = 2500 WHERE name = "Brelshaza G1/2" AND difficulty = "Normal";
+UPDATE raids SET gold = 1500 WHERE name = "Brelshaza G3/4" AND difficulty = "Normal";
+UPDATE raids SET gold = 2500 WHERE name = "Brelshaza G5/6" AND difficulty = "Normal";
+UPDATE raids SET gold = 3000 WHERE name = "Brelshaza G1/2" AND difficulty = "Hard";
+UPDATE raids SET gold = 2000 WHERE name = "Brelshaza G3/4" AND difficulty = "Hard";
+UPDATE raids SET gold = 3000 WHERE name = "Brelshaza G5/6" AND difficulty = "Hard";
+UPDATE raids SET gold = 3600 WHERE name = "Kayangel" AND difficulty = "Normal";
+UPDATE raids SET gold = 4800 WHERE name = "Kayangel" AND difficulty = "Hard";
+UPDATE raids SET gold = 4500 WHERE name = "Akkan" AND difficulty = "Normal";
+UPDATE raids SET gold = 6000 WHERE name = "Akkan" AND difficulty = "Hard";
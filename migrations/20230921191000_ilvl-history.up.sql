@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE character_ilvl_history (
+    id INTEGER PRIMARY KEY AUTO_INCREMENT,
+    character_id INTEGER NOT NULL,
+    item_level INTEGER NOT NULL,
+    recorded_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (character_id) REFERENCES characters(id) ON DELETE CASCADE
+);
+
+INSERT INTO character_ilvl_history (character_id, item_level)
+    SELECT id, item_level FROM characters;
@@ -0,0 +1,10 @@
+-- Add down migration script here
+ALTER TABLE users DROP COLUMN roster_level;
+
+ALTER TABLE characters
+    DROP COLUMN main,
+    DROP COLUMN note,
+    DROP COLUMN combat_power,
+    DROP COLUMN card_set,
+    DROP COLUMN stats,
+    DROP COLUMN build;
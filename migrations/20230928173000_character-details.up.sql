@@ -0,0 +1,10 @@
+-- Add up migration script here
+ALTER TABLE characters
+    ADD build VARCHAR(255) NOT NULL DEFAULT '',
+    ADD stats VARCHAR(255) NOT NULL DEFAULT '',
+    ADD card_set VARCHAR(255) NOT NULL DEFAULT '',
+    ADD combat_power INTEGER NOT NULL DEFAULT 0,
+    ADD note VARCHAR(1000) NOT NULL DEFAULT '',
+    ADD main BOOLEAN NOT NULL DEFAULT 0;
+
+ALTER TABLE users ADD roster_level INTEGER NOT NULL DEFAULT 0;
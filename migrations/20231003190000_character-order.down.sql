@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE characters DROP COLUMN sort_order;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+ALTER TABLE characters ADD sort_order INTEGER NOT NULL DEFAULT 0;
+
+UPDATE characters c
+JOIN (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY item_level DESC, id) AS pos
+    FROM characters
+) ranked
+ON ranked.id = c.id
+SET c.sort_order = ranked.pos;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE characters ADD version INTEGER NOT NULL DEFAULT 0;
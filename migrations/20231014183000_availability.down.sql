@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE availability_exceptions;
+DROP TABLE availability_windows;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE TABLE availability_windows (
+    id INTEGER PRIMARY KEY AUTO_INCREMENT,
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    weekday INTEGER NOT NULL,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    timezone VARCHAR(64) NOT NULL,
+    FOREIGN KEY (group_id, user_id) REFERENCES group_members(group_id, user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE availability_exceptions (
+    id INTEGER PRIMARY KEY AUTO_INCREMENT,
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    day DATE NOT NULL,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    available BOOLEAN NOT NULL,
+    timezone VARCHAR(64) NOT NULL,
+    FOREIGN KEY (group_id, user_id) REFERENCES group_members(group_id, user_id) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE users DROP COLUMN calendar_token;
+DROP TABLE group_event_characters;
+DROP TABLE group_events;
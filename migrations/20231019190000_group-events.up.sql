@@ -0,0 +1,24 @@
+-- Add up migration script here
+CREATE TABLE group_events (
+    id INTEGER PRIMARY KEY AUTO_INCREMENT,
+    group_id INTEGER NOT NULL,
+    raid_id INTEGER NOT NULL,
+    creator_id INTEGER NOT NULL,
+    starts_at TIMESTAMP NOT NULL,
+    duration INTEGER NOT NULL,
+    note VARCHAR(255) NOT NULL DEFAULT '',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (raid_id) REFERENCES raids(id) ON DELETE CASCADE,
+    FOREIGN KEY (creator_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE group_event_characters (
+    event_id INTEGER NOT NULL,
+    character_id INTEGER NOT NULL,
+    PRIMARY KEY (event_id, character_id),
+    FOREIGN KEY (event_id) REFERENCES group_events(id) ON DELETE CASCADE,
+    FOREIGN KEY (character_id) REFERENCES characters(id) ON DELETE CASCADE
+);
+
+ALTER TABLE users ADD calendar_token VARCHAR(64) NULL UNIQUE;
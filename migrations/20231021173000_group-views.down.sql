@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE group_view_hidden_raids;
+DROP TABLE group_views;
+ALTER TABLE raids DROP COLUMN obsolete;
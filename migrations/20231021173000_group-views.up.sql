@@ -0,0 +1,26 @@
+-- Add up migration script here
+ALTER TABLE raids ADD obsolete BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE raids SET obsolete = TRUE WHERE name IN ("Argos", "Valtan", "Vykas");
+
+CREATE TABLE group_views (
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    build VARCHAR(255) NOT NULL DEFAULT '',
+    mains BOOLEAN NOT NULL DEFAULT FALSE,
+    member VARCHAR(255) NOT NULL DEFAULT '',
+    hide_obsolete BOOLEAN NOT NULL DEFAULT FALSE,
+    min_eligible INTEGER NOT NULL DEFAULT 0,
+    sort VARCHAR(16) NOT NULL DEFAULT 'name',
+    PRIMARY KEY (group_id, user_id),
+    FOREIGN KEY (group_id, user_id) REFERENCES group_members(group_id, user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE group_view_hidden_raids (
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    raid_id INTEGER NOT NULL,
+    PRIMARY KEY (group_id, user_id, raid_id),
+    FOREIGN KEY (group_id, user_id) REFERENCES group_views(group_id, user_id) ON DELETE CASCADE,
+    FOREIGN KEY (raid_id) REFERENCES raids(id) ON DELETE CASCADE
+);
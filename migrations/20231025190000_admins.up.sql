@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE users ADD admin BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,21 @@
+-- Add down migration script here
+DROP TABLE group_view_hidden_raids;
+DROP TABLE group_views;
+DROP TABLE group_event_characters;
+DROP TABLE group_events;
+DROP TABLE availability_exceptions;
+DROP TABLE availability_windows;
+DROP TABLE character_ilvl_history;
+DROP TABLE resets;
+DROP TABLE character_tasks;
+DROP TABLE tasks;
+DROP TABLE blocked_players;
+DROP TABLE invites;
+DROP TABLE raid_prerequisites;
+DROP TABLE group_members;
+DROP TABLE groups;
+DROP TABLE user_raids;
+DROP TABLE raids;
+DROP TABLE characters;
+DROP TABLE classes;
+DROP TABLE users;
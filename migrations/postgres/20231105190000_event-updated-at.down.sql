@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE group_events DROP COLUMN updated_at;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE group_events ADD updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP;
+UPDATE group_events SET updated_at = created_at;
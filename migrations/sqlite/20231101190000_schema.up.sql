@@ -0,0 +1,238 @@
+-- Add up migration script here
+-- The schema of all MySQL migrations up to 20231025190000_admins in one step
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username VARCHAR(255) NOT NULL UNIQUE,
+    password_hash VARCHAR(255) NOT NULL,
+    roster_level INTEGER NOT NULL DEFAULT 0,
+    calendar_token VARCHAR(64) NULL UNIQUE,
+    admin BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE classes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    support SMALLINT NOT NULL
+);
+
+CREATE TABLE characters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    class_id INTEGER NOT NULL,
+    item_level INTEGER NOT NULL,
+    gold_earner BOOLEAN NOT NULL DEFAULT TRUE,
+    build VARCHAR(255) NOT NULL DEFAULT '',
+    stats VARCHAR(255) NOT NULL DEFAULT '',
+    card_set VARCHAR(255) NOT NULL DEFAULT '',
+    combat_power INTEGER NOT NULL DEFAULT 0,
+    note VARCHAR(1000) NOT NULL DEFAULT '',
+    main BOOLEAN NOT NULL DEFAULT FALSE,
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    version INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (class_id) REFERENCES classes(id) ON DELETE CASCADE
+);
+
+CREATE TABLE raids (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(255) NOT NULL,
+    difficulty VARCHAR(255) NOT NULL,
+    required_item_level INTEGER NOT NULL,
+    three_weekly SMALLINT NOT NULL,
+    gold INTEGER NOT NULL DEFAULT 0,
+    obsolete BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE user_raids (
+    user_id INTEGER NOT NULL,
+    character_id INTEGER NOT NULL,
+    raid_id INTEGER NOT NULL,
+    took_gold BOOLEAN NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (user_id, character_id, raid_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (character_id) REFERENCES characters(id) ON DELETE CASCADE,
+    FOREIGN KEY (raid_id) REFERENCES raids(id) ON DELETE CASCADE
+);
+
+CREATE TABLE groups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(255) NOT NULL,
+    creator_id INTEGER NOT NULL,
+    FOREIGN KEY (creator_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE group_members (
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    PRIMARY KEY (group_id, user_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE raid_prerequisites (
+    raid INTEGER NOT NULL,
+    requires INTEGER NOT NULL,
+    PRIMARY KEY (raid, requires),
+    FOREIGN KEY (raid) REFERENCES raids(id) ON DELETE CASCADE,
+    FOREIGN KEY (requires) REFERENCES raids(id) ON DELETE CASCADE
+);
+
+CREATE TABLE invites (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source INTEGER NOT NULL,
+    dest INTEGER NOT NULL,
+    group_id INTEGER NOT NULL,
+    FOREIGN KEY (source) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (dest) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+CREATE TABLE blocked_players (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source INTEGER NOT NULL,
+    dest INTEGER NOT NULL,
+    FOREIGN KEY (source) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (dest) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE tasks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(255) NOT NULL,
+    reset VARCHAR(16) NOT NULL CHECK (reset IN ('daily', 'weekly')),
+    rest_gain INTEGER NOT NULL DEFAULT 0,
+    rest_cost INTEGER NOT NULL DEFAULT 0,
+    rest_max INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE character_tasks (
+    character_id INTEGER NOT NULL,
+    task_id INTEGER NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT FALSE,
+    rest_bonus INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (character_id, task_id),
+    FOREIGN KEY (character_id) REFERENCES characters(id) ON DELETE CASCADE,
+    FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+CREATE TABLE resets (
+    kind VARCHAR(16) PRIMARY KEY CHECK (kind IN ('daily', 'weekly')),
+    last_reset TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE character_ilvl_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    character_id INTEGER NOT NULL,
+    item_level INTEGER NOT NULL,
+    recorded_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (character_id) REFERENCES characters(id) ON DELETE CASCADE
+);
+
+CREATE TABLE availability_windows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    weekday INTEGER NOT NULL,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    timezone VARCHAR(64) NOT NULL,
+    FOREIGN KEY (group_id, user_id) REFERENCES group_members(group_id, user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE availability_exceptions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    day DATE NOT NULL,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    available BOOLEAN NOT NULL,
+    timezone VARCHAR(64) NOT NULL,
+    FOREIGN KEY (group_id, user_id) REFERENCES group_members(group_id, user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE group_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    group_id INTEGER NOT NULL,
+    raid_id INTEGER NOT NULL,
+    creator_id INTEGER NOT NULL,
+    starts_at TIMESTAMP NOT NULL,
+    duration INTEGER NOT NULL,
+    note VARCHAR(255) NOT NULL DEFAULT '',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (raid_id) REFERENCES raids(id) ON DELETE CASCADE,
+    FOREIGN KEY (creator_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE group_event_characters (
+    event_id INTEGER NOT NULL,
+    character_id INTEGER NOT NULL,
+    PRIMARY KEY (event_id, character_id),
+    FOREIGN KEY (event_id) REFERENCES group_events(id) ON DELETE CASCADE,
+    FOREIGN KEY (character_id) REFERENCES characters(id) ON DELETE CASCADE
+);
+
+CREATE TABLE group_views (
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    build VARCHAR(255) NOT NULL DEFAULT '',
+    mains BOOLEAN NOT NULL DEFAULT FALSE,
+    member VARCHAR(255) NOT NULL DEFAULT '',
+    hide_obsolete BOOLEAN NOT NULL DEFAULT FALSE,
+    min_eligible INTEGER NOT NULL DEFAULT 0,
+    sort VARCHAR(16) NOT NULL DEFAULT 'name',
+    PRIMARY KEY (group_id, user_id),
+    FOREIGN KEY (group_id, user_id) REFERENCES group_members(group_id, user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE group_view_hidden_raids (
+    group_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    raid_id INTEGER NOT NULL,
+    PRIMARY KEY (group_id, user_id, raid_id),
+    FOREIGN KEY (group_id, user_id) REFERENCES group_views(group_id, user_id) ON DELETE CASCADE,
+    FOREIGN KEY (raid_id) REFERENCES raids(id) ON DELETE CASCADE
+);
+
+INSERT INTO classes (name, support) VALUES
+    ('Berserker', 0), ('Paladin', 1), ('Gunlancer', 0), ('Destroyer', 0), ('Slayer', 0),
+    ('Striker', 0), ('Wardancer', 0), ('Scrapper', 0), ('Soulfist', 0), ('Glaivier', 0),
+    ('Gunslinger', 0), ('Artillerist', 0), ('Deadeye', 0), ('Sharpshooter', 0), ('Machinist', 0),
+    ('Bard', 1), ('Sorceress', 0), ('Arcanist', 0), ('Summoner', 0),
+    ('Shadowhunter', 0), ('Deathblade', 0), ('Reaper', 0),
+    ('Artist', 1);
+
+INSERT INTO raids (name, difficulty, required_item_level, three_weekly, gold, obsolete) VALUES
+    ('Argos', 'Normal', 1370, 0, 1600, TRUE),
+    ('Valtan', 'Normal', 1415, 1, 1200, TRUE),
+    ('Valtan', 'Hard', 1445, 1, 1800, TRUE),
+    ('Vykas', 'Normal', 1430, 1, 1600, TRUE),
+    ('Vykas', 'Hard', 1460, 1, 2400, TRUE),
+    ('Kakul-Saydon', 'Normal', 1475, 1, 3000, FALSE),
+    ('Brelshaza G1/2', 'Normal', 1490, 1, 2500, FALSE),
+    ('Brelshaza G3/4', 'Normal', 1500, 0, 1500, FALSE),
+    ('Brelshaza G5/6', 'Normal', 1520, 0, 2500, FALSE),
+    ('Brelshaza G1/2', 'Hard', 1540, 1, 3000, FALSE),
+    ('Brelshaza G3/4', 'Hard', 1550, 0, 2000, FALSE),
+    ('Brelshaza G5/6', 'Hard', 1560, 0, 3000, FALSE),
+    ('Kayangel', 'Normal', 1540, 1, 3600, FALSE),
+    ('Kayangel', 'Hard', 1580, 1, 4800, FALSE),
+    ('Akkan', 'Normal', 1580, 1, 4500, FALSE),
+    ('Akkan', 'Hard', 1600, 1, 6000, FALSE);
+
+INSERT INTO raid_prerequisites (raid, requires)
+    SELECT r.id, rr.id
+    FROM raids r
+    CROSS JOIN raids rr
+    WHERE (r.name = 'Brelshaza G3/4' AND rr.name = 'Brelshaza G1/2')
+        OR (r.name = 'Brelshaza G5/6' AND rr.name = 'Brelshaza G3/4');
+
+INSERT INTO resets (kind) VALUES ('daily'), ('weekly');
+
+INSERT INTO tasks (name, reset, rest_gain, rest_cost, rest_max) VALUES
+    ('Chaos Dungeon', 'daily', 20, 40, 100),
+    ('Guardian Raid', 'daily', 10, 20, 100),
+    ('Una''s Tasks', 'daily', 10, 20, 100),
+    ('Weekly Una''s Tasks', 'weekly', 0, 0, 0),
+    ('Abyss Dungeon', 'weekly', 0, 0, 0);
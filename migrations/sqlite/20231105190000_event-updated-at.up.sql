@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- SQLite can only add columns with a constant default
+ALTER TABLE group_events ADD updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+UPDATE group_events SET updated_at = created_at;
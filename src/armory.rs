@@ -0,0 +1,223 @@
+//! Parser for rosters pasted from armory sites like lostark.bible.
+//!
+//! Those pages are copied in many shapes: one character per line (`Name Bard 1,620.83`),
+//! tables with tabs, or blocks where name, class and item level are on separate lines.
+//! Every line is split into tokens which are classified as item level, class or name, and a
+//! character is emitted as soon as all three were seen.
+
+#[cfg(test)]
+mod tests;
+
+use crate::data::Class;
+
+/// Item levels below this are treated as character or roster levels. Roster levels reach the
+/// hundreds, while no raid in the rules can be entered below 1000 anyway.
+const MIN_ITEM_LEVEL: f64 = 1000.0;
+const MAX_ITEM_LEVEL: f64 = 2000.0;
+
+/// Words shorter than this have to match a class exactly
+const FUZZY_MIN_LEN: usize = 8;
+
+/// Column headers and labels that show up in copied tables
+const STOP_WORDS: [&str; 8] = ["item", "level", "ilvl", "name", "class", "character", "roster", "server"];
+
+/// Names used by other regions or sites for the classes in our table
+const CLASS_ALIASES: [(&str, &str); 16] = [
+    ("warlord", "Gunlancer"),
+    ("holyknight", "Paladin"),
+    ("battlemaster", "Wardancer"),
+    ("infighter", "Scrapper"),
+    ("forcemaster", "Soulfist"),
+    ("lancemaster", "Glaivier"),
+    ("devilhunter", "Deadeye"),
+    ("blaster", "Artillerist"),
+    ("hawkeye", "Sharpshooter"),
+    ("scouter", "Machinist"),
+    ("blade", "Deathblade"),
+    ("demonic", "Shadowhunter"),
+    ("elementalist", "Sorceress"),
+    ("yinyangshi", "Artist"),
+    ("gunslinger", "Gunslinger"),
+    ("slayer", "Slayer"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedChar {
+    pub name: String,
+    pub class_id: i32,
+    pub class: String,
+    pub item_level: i32,
+    /// The text the class was recognized from if it was not an exact match
+    pub fuzzy_class: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedRoster {
+    pub characters: Vec<ParsedChar>,
+    /// Lines that did not contribute to any character
+    pub ignored: Vec<String>,
+}
+
+#[derive(Default)]
+struct Pending {
+    name: Option<String>,
+    class: Option<(i32, String, Option<String>)>,
+    item_level: Option<i32>,
+    lines: Vec<String>,
+}
+
+/// Parses a pasted roster, mapping class names onto `classes`
+pub fn parse_roster(text: &str, classes: &[Class]) -> ParsedRoster {
+    let mut roster = ParsedRoster::default();
+    let mut pending = Pending::default();
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let mut used = false;
+        let mut tokens = line.split(|c: char| c.is_whitespace() || c == '|' || c == ';')
+            .filter(|t| !t.is_empty())
+            .peekable();
+
+        while let Some(token) = tokens.next() {
+            let lower = token.to_lowercase();
+
+            // Level markers like "Lv.60" or "Lv 60" are not item levels
+            if let Some(rest) = lower.strip_prefix("lv") {
+                let rest = rest.trim_start_matches('.');
+                if rest.is_empty() {
+                    tokens.next();
+                    continue;
+                }
+                if rest.chars().all(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+            }
+
+            if STOP_WORDS.contains(&lower.trim_end_matches(':')) {
+                continue;
+            }
+
+            if let Some(ilvl) = parse_item_level(token) {
+                if pending.item_level.is_some() {
+                    flush(&mut pending, &mut roster);
+                }
+                pending.item_level = Some(ilvl);
+                used = true;
+            } else if let Some(class) = match_class(token, classes)
+                .or_else(|| tokens.peek().and_then(|next| match_class(&format!("{}{}", token, next), classes)).inspect(|_| {
+                    tokens.next();
+                }))
+            {
+                if pending.class.is_some() {
+                    flush(&mut pending, &mut roster);
+                }
+                pending.class = Some(class);
+                used = true;
+            } else if is_name(token) {
+                if pending.name.is_some() {
+                    flush(&mut pending, &mut roster);
+                }
+                pending.name = Some(token.to_string());
+                used = true;
+            }
+
+            if pending.name.is_some() && pending.class.is_some() && pending.item_level.is_some() {
+                flush(&mut pending, &mut roster);
+            }
+        }
+
+        if !used {
+            roster.ignored.push(line.to_string());
+        } else if pending.name.is_some() || pending.class.is_some() || pending.item_level.is_some() {
+            pending.lines.push(line.to_string());
+        }
+    }
+
+    flush(&mut pending, &mut roster);
+
+    roster
+}
+
+/// Emits the pending character if it is complete, otherwise reports its lines as ignored
+fn flush(pending: &mut Pending, roster: &mut ParsedRoster) {
+    let p = std::mem::take(pending);
+
+    match (p.name, p.class, p.item_level) {
+        (Some(name), Some((class_id, class, fuzzy_class)), Some(item_level)) => {
+            roster.characters.push(ParsedChar { name, class_id, class, item_level, fuzzy_class });
+        },
+        _ => roster.ignored.extend(p.lines),
+    }
+}
+
+/// Parses item levels like `1620`, `1,620.83` or `1620.83`
+fn parse_item_level(token: &str) -> Option<i32> {
+    let cleaned: String = token.chars().filter(|c| *c != ',').collect();
+    let value: f64 = cleaned.parse().ok()?;
+
+    if (MIN_ITEM_LEVEL..=MAX_ITEM_LEVEL).contains(&value) {
+        Some(value.floor() as i32)
+    } else {
+        None
+    }
+}
+
+/// Character names consist of letters and digits only and start with a letter
+fn is_name(token: &str) -> bool {
+    let mut chars = token.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic())
+        && token.chars().all(char::is_alphanumeric)
+        && token.chars().count() <= 16
+}
+
+/// Finds the class a token refers to, allowing a single typo in long class names
+fn match_class(token: &str, classes: &[Class]) -> Option<(i32, String, Option<String>)> {
+    let normalized: String = token.chars().filter(|c| c.is_alphabetic()).collect::<String>().to_lowercase();
+    if normalized.len() < 4 {
+        return None;
+    }
+
+    let find = |name: &str| classes.iter().find(|c| c.name.eq_ignore_ascii_case(name));
+
+    if let Some(c) = find(&normalized) {
+        return Some((c.id, c.name.clone(), None));
+    }
+
+    if let Some(c) = CLASS_ALIASES.iter().find(|(alias, _)| *alias == normalized).and_then(|(_, name)| find(name)) {
+        return Some((c.id, c.name.clone(), Some(token.to_string())));
+    }
+
+    // Only long words may contain a typo, otherwise names like "Bardo" would turn into classes
+    if normalized.chars().count() < FUZZY_MIN_LEN {
+        return None;
+    }
+
+    let candidates = classes.iter()
+        .map(|c| (c.name.as_str(), c))
+        .chain(CLASS_ALIASES.iter().filter_map(|(alias, name)| find(name).map(|c| (*alias, c))));
+
+    // The first letter has to be right as well
+    let first = normalized.chars().next();
+
+    candidates
+        .filter(|(name, _)| name.to_lowercase().chars().next() == first)
+        .map(|(name, c)| (levenshtein(&normalized, &name.to_lowercase()), c))
+        .filter(|(distance, _)| *distance <= 1)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| (c.id, c.name.clone(), Some(token.to_string())))
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+
+    prev[b.len()]
+}
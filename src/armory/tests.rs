@@ -0,0 +1,132 @@
+//! Rosters as they come out of copying armory pages in a browser.
+
+use super::{parse_roster, ParsedChar};
+use crate::data::Class;
+
+fn classes() -> Vec<Class> {
+    ["Berserker", "Paladin", "Gunlancer", "Sharpshooter", "Bard", "Sorceress", "Artist", "Deathblade"]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| Class { id: i as i32 + 1, name: name.to_string(), support: 0 })
+        .collect()
+}
+
+fn summary(chars: &[ParsedChar]) -> Vec<(&str, &str, i32)> {
+    chars.iter().map(|c| (c.name.as_str(), c.class.as_str(), c.item_level)).collect()
+}
+
+#[test]
+fn roster_page_blocks() {
+    let text = "\
+Roster Level 263
+Sagittarius
+Sharpshooter
+Lv. 60
+1,620.83
+Wintermelody
+Bard
+Lv. 60
+1,600.00
+Hammertime
+Paladin
+Lv. 55
+1,340
+";
+
+    let roster = parse_roster(text, &classes());
+
+    assert_eq!(summary(&roster.characters), vec![
+        ("Sagittarius", "Sharpshooter", 1620),
+        ("Wintermelody", "Bard", 1600),
+        ("Hammertime", "Paladin", 1340),
+    ]);
+    assert!(roster.characters.iter().all(|c| c.fuzzy_class.is_none()));
+}
+
+#[test]
+fn copied_table() {
+    let text = "\
+Name\tClass\tItem Level
+Sagittarius\tSharpshooter\t1620.83
+Brickwall\tGunlancer\t1580.00
+Doodles\tArtist\t1445.50
+";
+
+    let roster = parse_roster(text, &classes());
+
+    assert_eq!(summary(&roster.characters), vec![
+        ("Sagittarius", "Sharpshooter", 1620),
+        ("Brickwall", "Gunlancer", 1580),
+        ("Doodles", "Artist", 1445),
+    ]);
+    assert_eq!(roster.ignored, vec!["Name\tClass\tItem Level"]);
+}
+
+#[test]
+fn one_character_per_line() {
+    let text = "\
+Sagittarius Sharpshooter 1,620.83
+Wintermelody | Bard | 1,600
+Lv.60 Hammertime Paladin 1340
+";
+
+    let roster = parse_roster(text, &classes());
+
+    assert_eq!(summary(&roster.characters), vec![
+        ("Sagittarius", "Sharpshooter", 1620),
+        ("Wintermelody", "Bard", 1600),
+        ("Hammertime", "Paladin", 1340),
+    ]);
+}
+
+#[test]
+fn roster_and_character_levels_are_not_item_levels() {
+    let text = "\
+Roster Level 263
+Sagittarius
+Sharpshooter
+Lv 60
+";
+
+    let roster = parse_roster(text, &classes());
+
+    assert!(roster.characters.is_empty());
+    assert!(roster.ignored.iter().any(|l| l.contains("Sagittarius")));
+}
+
+#[test]
+fn names_close_to_a_class_stay_names() {
+    let text = "\
+Bardo Bard 1580
+Artisto Artist 1540
+Pala Paladin 1600
+";
+
+    let roster = parse_roster(text, &classes());
+
+    assert_eq!(summary(&roster.characters), vec![
+        ("Bardo", "Bard", 1580),
+        ("Artisto", "Artist", 1540),
+        ("Pala", "Paladin", 1600),
+    ]);
+}
+
+#[test]
+fn typos_and_aliases_are_flagged() {
+    let text = "\
+Brickwall Gunlancr 1580
+Sunshine Holy Knight 1560
+Slicer Blade 1500
+";
+
+    let roster = parse_roster(text, &classes());
+
+    assert_eq!(summary(&roster.characters), vec![
+        ("Brickwall", "Gunlancer", 1580),
+        ("Sunshine", "Paladin", 1560),
+        ("Slicer", "Deathblade", 1500),
+    ]);
+    assert_eq!(roster.characters[0].fuzzy_class.as_deref(), Some("Gunlancr"));
+    assert!(roster.characters[1].fuzzy_class.is_some());
+    assert_eq!(roster.characters[2].fuzzy_class.as_deref(), Some("Blade"));
+}
@@ -0,0 +1,208 @@
+//! Command line of the server binary.
+
+use chrono::Utc;
+
+use crate::config::Config;
+use crate::crypto::{argon2_hash_text, random_token};
+use crate::migrate;
+use crate::repo::{RepoError, Repository};
+use crate::reset::{self, ResetKind};
+
+pub const USAGE: &str = "Usage: la-website-2 [COMMAND]
+
+Commands:
+  serve                        Run the web server (default)
+  migrate status               List migrations and whether they are applied
+  migrate up                   Apply all pending migrations
+  migrate down                 Revert the most recent migration
+  user reset-password <name>   Set a new random password and print it
+  user delete <name>           Delete a user with their characters and the groups they own
+  user promote <name>          Allow a user to edit game data
+  user demote <name>           Take admin rights away from a user
+  reset-week                   Run the weekly reset now
+  stats                        Show user, character and group counts
+  groups                       List all groups with their owner and size
+
+The configuration is read from config.toml, or the file in LA_CONFIG.";
+
+pub enum Command {
+    Help,
+    Serve,
+    Migrate(MigrateCommand),
+    User(UserCommand, String),
+    ResetWeek,
+    Stats,
+    Groups,
+}
+
+pub enum MigrateCommand {
+    Status,
+    Up,
+    Down,
+}
+
+pub enum UserCommand {
+    ResetPassword,
+    Delete,
+    Promote,
+    Demote,
+}
+
+impl Command {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+        let command = match args.next().as_deref() {
+            None | Some("serve") => Command::Serve,
+            Some("help" | "-h" | "--help") => Command::Help,
+            Some("migrate") => match args.next().as_deref() {
+                Some("status") => Command::Migrate(MigrateCommand::Status),
+                Some("up") => Command::Migrate(MigrateCommand::Up),
+                Some("down") => Command::Migrate(MigrateCommand::Down),
+                Some(other) => return Err(format!("Unknown migrate command {}", other)),
+                None => return Err("migrate needs a command".to_string()),
+            },
+            Some("user") => {
+                let command = match args.next().as_deref() {
+                    Some("reset-password") => UserCommand::ResetPassword,
+                    Some("delete") => UserCommand::Delete,
+                    Some("promote") => UserCommand::Promote,
+                    Some("demote") => UserCommand::Demote,
+                    Some(other) => return Err(format!("Unknown user command {}", other)),
+                    None => return Err("user needs a command".to_string()),
+                };
+
+                match args.next() {
+                    Some(name) => Command::User(command, name),
+                    None => return Err("user commands need a username".to_string()),
+                }
+            },
+            Some("reset-week") => Command::ResetWeek,
+            Some("stats") => Command::Stats,
+            Some("groups") => Command::Groups,
+            Some(other) => return Err(format!("Unknown command {}", other)),
+        };
+
+        match args.next() {
+            Some(extra) => Err(format!("Unexpected argument {}", extra)),
+            None => Ok(command),
+        }
+    }
+
+    /// Runs every command except `serve`
+    pub async fn run(self, repo: &dyn Repository, config: &Config) -> Result<(), String> {
+        match self {
+            Command::Help | Command::Serve => Ok(()),
+            Command::Migrate(command) => run_migrate(repo, command).await,
+            Command::User(command, name) => run_user(repo, command, &name).await,
+            Command::ResetWeek => {
+                let boundary = ResetKind::Weekly.last_boundary(Utc::now(), &config.reset);
+                reset::run_reset(repo, ResetKind::Weekly, boundary, true).await.map_err(|e| e.to_string())?;
+                println!("Weekly reset done");
+                Ok(())
+            },
+            Command::Stats => stats(repo).await.map_err(|e| e.to_string()),
+            Command::Groups => groups(repo).await.map_err(|e| e.to_string()),
+        }
+    }
+}
+
+async fn run_migrate(repo: &dyn Repository, command: MigrateCommand) -> Result<(), String> {
+    match command {
+        MigrateCommand::Status => {
+            let status = migrate::status(repo).await.map_err(|e| e.to_string())?;
+
+            for m in status {
+                let state = match (m.unknown, m.applied) {
+                    (true, _) => "unknown to this binary",
+                    (false, Some(true)) => "applied",
+                    (false, Some(false)) => "failed",
+                    (false, None) => "pending",
+                };
+                println!("{} {:<24} {}", m.version, m.description, state);
+            }
+        },
+        MigrateCommand::Up => {
+            migrate::check(repo).await?;
+            migrate::up(repo).await.map_err(|e| e.to_string())?;
+            println!("All migrations applied");
+        },
+        MigrateCommand::Down => {
+            migrate::check(repo).await?;
+            match migrate::down(repo).await.map_err(|e| e.to_string())? {
+                Some(v) => println!("Reverted migration {}", v),
+                None => println!("No migration to revert"),
+            }
+        },
+    }
+
+    Ok(())
+}
+
+async fn run_user(repo: &dyn Repository, command: UserCommand, name: &str) -> Result<(), String> {
+    let user = match repo.user_by_name(name).await.map_err(|e| e.to_string())? {
+        Some(v) => v,
+        None => return Err(format!("There is no user {}", name)),
+    };
+
+    match command {
+        UserCommand::ResetPassword => {
+            let password = random_token()[..20].to_string();
+            let hash = argon2_hash_text(&password).map_err(|e| e.to_string())?;
+
+            repo.set_password(user.id, &hash).await.map_err(|e| e.to_string())?;
+
+            // Logged in sessions live in the signed cookie and can't be revoked from here
+            println!("New password of {}: {}", user.username, password);
+        },
+        UserCommand::Delete => {
+            let owned = repo.owned_groups(user.id).await.map_err(|e| e.to_string())?;
+
+            repo.delete_user(user.id).await.map_err(|e| e.to_string())?;
+
+            println!("Deleted {}", user.username);
+            for g in owned {
+                println!("Deleted their group {}", g);
+            }
+        },
+        UserCommand::Promote | UserCommand::Demote => {
+            let admin = matches!(command, UserCommand::Promote);
+
+            if user.admin == admin {
+                match admin {
+                    true => println!("{} is already an admin", user.username),
+                    false => println!("{} is not an admin", user.username),
+                }
+                return Ok(());
+            }
+
+            repo.set_admin(user.id, admin).await.map_err(|e| e.to_string())?;
+
+            match admin {
+                true => println!("{} is now an admin", user.username),
+                false => println!("{} is no longer an admin", user.username),
+            }
+        },
+    }
+
+    Ok(())
+}
+
+async fn stats(repo: &dyn Repository) -> Result<(), RepoError> {
+    let s = repo.stats(Utc::now()).await?;
+
+    println!("Users:             {}", s.users);
+    println!("Admins:            {}", s.admins);
+    println!("Characters:        {}", s.characters);
+    println!("Groups:            {}", s.groups);
+    println!("Clears:            {}", s.clears);
+    println!("Upcoming raids:    {}", s.events);
+
+    Ok(())
+}
+
+async fn groups(repo: &dyn Repository) -> Result<(), RepoError> {
+    for g in repo.all_groups().await? {
+        println!("{:>6} {:<32} {:<24} {} members", g.id, g.name, g.owner, g.members);
+    }
+
+    Ok(())
+}
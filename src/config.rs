@@ -0,0 +1,372 @@
+//! Deployment settings.
+//!
+//! Read from a TOML file (`config.toml`, or the path in `LA_CONFIG`) and overridden by environment
+//! variables. `LA_<SECTION>__<KEY>` overrides a single key, e.g. `LA_DATABASE__MAX_CONNECTIONS=10`.
+//! `DATABASE_URL` and `COOKIE_SECRET` are still read so secrets can stay out of the file.
+//! Every setting has a default except the two secrets.
+
+use std::fmt;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+
+use chrono::Weekday;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::logging::LogFormat;
+use crate::proxy::{Cidr, TrustedProxies};
+
+const DEFAULT_PATH: &str = "config.toml";
+const ENV_PREFIX: &str = "LA_";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub tls: TlsConfig,
+    pub proxy: ProxyConfig,
+    pub metrics: MetricsConfig,
+    pub database: DatabaseConfig,
+    pub session: SessionConfig,
+    pub reset: ResetConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Addresses to listen on, e.g. `0.0.0.0:8443`
+    pub bind: Vec<String>,
+    /// Glob of the tera templates
+    pub templates: String,
+    pub static_dir: PathBuf,
+    /// Seconds to let open requests and then background jobs finish on shutdown
+    pub shutdown_timeout: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: vec!["0.0.0.0:8443".to_string()],
+            templates: "templates/**/*.html".to_string(),
+            static_dir: PathBuf::from("./static"),
+            shutdown_timeout: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Serve HTTPS. Without it the server speaks plain HTTP, e.g. behind a proxy that terminates TLS.
+    pub enabled: bool,
+    pub key: PathBuf,
+    pub cert: PathBuf,
+    /// Address of an extra listener that redirects plain HTTP requests to HTTPS
+    pub redirect_http: Option<String>,
+    /// Seconds between checks whether the key or certificate changed, 0 to only reload on SIGHUP
+    pub reload_interval: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            enabled: true,
+            key: PathBuf::from("key.pem"),
+            cert: PathBuf::from("cert.pem"),
+            redirect_http: None,
+            reload_interval: 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyConfig {
+    /// Addresses or ranges of reverse proxies whose `Forwarded` and `X-Forwarded-For` headers are
+    /// believed, e.g. `["127.0.0.1", "10.0.0.0/8"]`
+    pub trusted: TrustedProxies,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Addresses or ranges that may scrape `/metrics`, e.g. `["127.0.0.1"]`. Behind a trusted
+    /// proxy this is the client address the proxy forwarded.
+    pub allow: Vec<Cidr>,
+    /// Token a scraper can send as `Authorization: Bearer <token>` from any address
+    pub token: Option<Secret<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// `mysql://`, `postgres://` or `sqlite://` url, see [`crate::repo`]
+    pub url: Option<Secret<String>>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// Seconds to wait for a free connection before a request fails
+    pub acquire_timeout: u64,
+    /// Apply pending migrations when the server starts
+    pub migrate_on_start: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: None,
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout: 30,
+            migrate_on_start: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Key to sign and encrypt the session cookie, at least 64 bytes
+    pub secret: Option<Secret<String>>,
+    pub cookie_name: String,
+    /// Keep users logged in for this many days. Without it the session ends with the browser.
+    pub max_age_days: Option<i64>,
+    /// Only send the cookie over HTTPS. Defaults to on when serving TLS or behind a trusted proxy.
+    pub cookie_secure: Option<bool>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            secret: None,
+            cookie_name: "id".to_string(),
+            max_age_days: None,
+            cookie_secure: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResetConfig {
+    /// Hour (UTC) at which the daily and weekly resets happen
+    pub hour: u32,
+    /// Day of the weekly reset, e.g. `Wed`
+    pub weekly_day: Weekday,
+    /// Seconds between checks for due resets
+    pub check_interval: u64,
+}
+
+impl Default for ResetConfig {
+    fn default() -> Self {
+        ResetConfig {
+            hour: 10,
+            weekly_day: Weekday::Wed,
+            check_interval: 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Log filter if `RUST_LOG` is not set
+    pub level: String,
+    /// `text` or `json`
+    pub format: LogFormat,
+    /// Format of the access log in the text format, see `actix_web::middleware::Logger`.
+    /// `%{client}xi` is the client address, also behind a trusted proxy.
+    pub access_log: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+            access_log: "%{client}xi: %r, %s".to_string(),
+        }
+    }
+}
+
+/// Everything that is wrong with the configuration, so it can be fixed in one go
+#[derive(Debug)]
+pub struct ConfigError(Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for e in self.0.iter() {
+            writeln!(f, "  - {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads the configuration file and environment and validates what every command needs.
+    /// The web server checks the rest with [`Config::validate_serve`].
+    pub fn load() -> Result<Config, ConfigError> {
+        // The .env file is optional, the environment may be set by the service manager
+        dotenv::dotenv().ok();
+
+        let (path, required) = match std::env::var("LA_CONFIG") {
+            Ok(v) => (PathBuf::from(v), true),
+            Err(_) => (PathBuf::from(DEFAULT_PATH), false),
+        };
+
+        let mut table = match std::fs::read_to_string(&path) {
+            Ok(v) => v.parse::<toml::Table>()
+                .map_err(|e| ConfigError(vec![format!("{}: {}", path.display(), e)]))?,
+            Err(e) if required || e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(ConfigError(vec![format!("Could not read {}: {}", path.display(), e)]));
+            },
+            Err(_) => toml::Table::new(),
+        };
+
+        let mut errors = Vec::new();
+
+        for (key, value) in std::env::vars() {
+            let Some(name) = key.strip_prefix(ENV_PREFIX) else { continue };
+            if name == "CONFIG" {
+                continue;
+            }
+
+            if let Err(e) = apply_override(&mut table, name, &value) {
+                errors.push(format!("{}: {}", key, e));
+            }
+        }
+
+        for (key, section, name) in [("DATABASE_URL", "database", "url"), ("COOKIE_SECRET", "session", "secret")] {
+            if let Ok(value) = std::env::var(key) {
+                section_mut(&mut table, section).insert(name.to_string(), toml::Value::String(value));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(ConfigError(errors));
+        }
+
+        let config: Config = toml::Value::Table(table).try_into()
+            .map_err(|e: toml::de::Error| ConfigError(vec![e.to_string()]))?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks the sections every command needs
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+
+        if self.database.url.is_none() {
+            errors.push("database.url is not set, e.g. with DATABASE_URL".to_string());
+        }
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.database.min_connections > self.database.max_connections {
+            errors.push("database.min_connections is larger than database.max_connections".to_string());
+        }
+
+        if self.reset.hour > 23 {
+            errors.push("reset.hour must be between 0 and 23".to_string());
+        }
+        if self.reset.check_interval == 0 {
+            errors.push("reset.check_interval must be at least 1 second".to_string());
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError(errors)),
+        }
+    }
+
+    /// Checks the sections only the web server needs, so the admin commands also run on hosts
+    /// without certificates or a session secret
+    pub fn validate_serve(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+
+        if self.server.bind.is_empty() {
+            errors.push("server.bind needs at least one address".to_string());
+        }
+        for addr in self.server.bind.iter() {
+            if let Err(e) = addr.to_socket_addrs() {
+                errors.push(format!("server.bind: {} is not a valid address: {}", addr, e));
+            }
+        }
+        if !self.server.static_dir.is_dir() {
+            errors.push(format!("server.static_dir: {} is not a directory", self.server.static_dir.display()));
+        }
+
+        if self.tls.enabled {
+            for (name, path) in [("tls.key", &self.tls.key), ("tls.cert", &self.tls.cert)] {
+                if !path.is_file() {
+                    errors.push(format!("{}: {} does not exist", name, path.display()));
+                }
+            }
+        }
+        match &self.tls.redirect_http {
+            Some(_) if !self.tls.enabled => errors.push("tls.redirect_http needs tls.enabled".to_string()),
+            Some(addr) => if let Err(e) = addr.to_socket_addrs() {
+                errors.push(format!("tls.redirect_http: {} is not a valid address: {}", addr, e));
+            },
+            None => (),
+        }
+
+        if self.metrics.token.as_ref().is_some_and(|t| t.expose_secret().is_empty()) {
+            errors.push("metrics.token must not be empty".to_string());
+        }
+
+        match &self.session.secret {
+            None => errors.push("session.secret is not set, e.g. with COOKIE_SECRET".to_string()),
+            Some(s) if s.expose_secret().len() < 64 => errors.push("session.secret must be at least 64 bytes long".to_string()),
+            Some(_) => (),
+        }
+        if self.session.cookie_name.is_empty() {
+            errors.push("session.cookie_name must not be empty".to_string());
+        }
+        if self.session.max_age_days.is_some_and(|d| d <= 0) {
+            errors.push("session.max_age_days must be positive".to_string());
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError(errors)),
+        }
+    }
+}
+
+impl SessionConfig {
+    pub fn secure(&self, tls: &TlsConfig, proxy: &ProxyConfig) -> bool {
+        self.cookie_secure.unwrap_or(tls.enabled || !proxy.trusted.is_empty())
+    }
+}
+
+fn section_mut<'a>(table: &'a mut toml::Table, section: &str) -> &'a mut toml::Table {
+    let entry = table.entry(section.to_string()).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if !entry.is_table() {
+        *entry = toml::Value::Table(toml::Table::new());
+    }
+    entry.as_table_mut().unwrap()
+}
+
+/// Sets `SECTION__KEY` in the table. The value is read as TOML, so numbers, booleans and lists
+/// keep their type, and anything else is taken as a string.
+fn apply_override(table: &mut toml::Table, name: &str, raw: &str) -> Result<(), String> {
+    let Some((section, key)) = name.split_once("__") else {
+        return Err("expected LA_<SECTION>__<KEY>".to_string());
+    };
+
+    let value = format!("v = {}", raw)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+
+    section_mut(table, &section.to_lowercase()).insert(key.to_lowercase(), value);
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use argon2::{Argon2, PasswordHasher, PasswordHash, PasswordVerifier};
 use argon2::password_hash::{SaltString, errors::Error};
-use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
 
 #[derive(Clone)]
 pub struct CookieSessionSecret{
@@ -19,3 +19,10 @@ pub fn argon2_verify_password(password: &str, hash: &str) -> Result<(), Error>{
     let hash = PasswordHash::new(hash)?;
     Argon2::default().verify_password(password.as_bytes(), &hash)
 }
+
+/// Generates a random hex token that is hard to guess, e.g. for secret urls
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
@@ -6,20 +7,39 @@ pub struct User {
     pub password: String,
 }
 
-#[derive(Deserialize)]
-pub struct Cookie {
-    pub id: String,
-    pub user_id: i32,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-}
+/// Amount of characters per roster that can earn gold each week
+pub const GOLD_EARNERS: usize = 6;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
 pub struct Character {
     pub id: i32,
     pub user_id: i32,
     pub name: String,
     pub class_id: i32,
     pub item_level: i32,
+    /// Whether the character counts towards the six gold earning characters of a roster
+    #[serde(default)]
+    pub gold_earner: bool,
+    /// Engraving build or spec, e.g. "Reflux"
+    #[serde(default)]
+    pub build: String,
+    /// Main combat stats, e.g. "Crit/Swift"
+    #[serde(default)]
+    pub stats: String,
+    #[serde(default)]
+    pub card_set: String,
+    #[serde(default)]
+    pub combat_power: i32,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub main: bool,
+    /// User defined position in the roster
+    #[serde(default)]
+    pub sort_order: i32,
+    /// Incremented on every edit to detect concurrent changes
+    #[serde(default)]
+    pub version: i32,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -29,37 +49,73 @@ pub struct Class {
     pub support: u8,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Raid {
     pub id: i32,
     pub name: String,
     pub difficulty: String,
     pub required_item_level: i32,
+    pub three_weekly: u8,
+    pub gold: i32,
+    /// Old content that can be hidden from the group overview
+    pub obsolete: bool,
 }
 
-#[derive(Deserialize)]
-pub struct UserRaid {
-    pub user_id: i32,
+/// A non-raid activity like chaos dungeons that is reset daily or weekly
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow)]
+pub struct Task {
+    pub id: i32,
+    pub name: String,
+    pub reset: String,
+    pub rest_gain: i32,
+    pub rest_cost: i32,
+    pub rest_max: i32,
+}
+
+#[derive(Deserialize, Serialize, sqlx::FromRow)]
+pub struct CharacterTask {
     pub character_id: i32,
-    pub raid_id: i32,
+    pub task_id: i32,
+    pub completed: bool,
+    pub rest_bonus: i32,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct Group {
+/// A recurring weekly time a group member can raid, in the members timezone.
+/// An end before the start wraps into the next day.
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow)]
+pub struct AvailabilityWindow {
     pub id: i32,
-    pub name: String,
-    pub creator_id: i32,
+    pub group_id: i32,
+    pub user_id: i32,
+    /// Days since monday
+    pub weekday: i32,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub timezone: String,
 }
 
-#[derive(Deserialize)]
-pub struct GroupMember {
+/// Extra availability or an absence on a single day, overriding the weekly windows
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow)]
+pub struct AvailabilityException {
+    pub id: i32,
     pub group_id: i32,
     pub user_id: i32,
+    pub day: NaiveDate,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub available: bool,
+    pub timezone: String,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct Invite {
-    pub source: i32,
-    pub dest: i32,
+/// A raid scheduled by a group. `duration` is in minutes.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GroupEvent {
+    pub id: i32,
     pub group_id: i32,
+    pub raid_id: i32,
+    pub creator_id: i32,
+    pub starts_at: DateTime<Utc>,
+    pub duration: i32,
+    pub note: String,
+    pub created_at: DateTime<Utc>,
 }
@@ -0,0 +1,47 @@
+//! Cache of the static game data.
+//!
+//! Classes, raids and raid prerequisites only change with migrations or through the admin page, so
+//! they are loaded once at startup and shared by every worker. Whoever changes them calls
+//! [`GameData::reload`] afterwards.
+
+use std::sync::{Arc, RwLock};
+
+use crate::data::Class;
+use crate::repo::{RepoError, Repository};
+use crate::rules::RaidRules;
+
+pub struct StaticData {
+    /// All classes, ordered by name
+    pub classes: Vec<Class>,
+    pub rules: RaidRules,
+}
+
+pub struct GameData {
+    current: RwLock<Arc<StaticData>>,
+}
+
+impl GameData {
+    pub async fn load(repo: &dyn Repository) -> Result<Self, RepoError> {
+        Ok(GameData { current: RwLock::new(Arc::new(fetch(repo).await?)) })
+    }
+
+    /// The current data. Handlers keep the returned snapshot for the whole request, so a reload
+    /// in between does not mix old and new data.
+    pub fn get(&self) -> Arc<StaticData> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Reads the data from the database again and replaces the cached copy
+    pub async fn reload(&self, repo: &dyn Repository) -> Result<(), RepoError> {
+        let data = Arc::new(fetch(repo).await?);
+        *self.current.write().unwrap() = data;
+        Ok(())
+    }
+}
+
+async fn fetch(repo: &dyn Repository) -> Result<StaticData, RepoError> {
+    let classes = repo.classes().await?;
+    let rules = RaidRules::new(repo.raids().await?, repo.prerequisites().await?);
+
+    Ok(StaticData { classes, rules })
+}
@@ -0,0 +1,86 @@
+//! Minimal iCalendar (RFC 5545) writer for the raid calendar feeds.
+
+use chrono::{DateTime, Utc};
+
+/// Content lines longer than this many octets have to be folded
+const LINE_LIMIT: usize = 75;
+
+pub struct Event {
+    /// Globally unique and stable across feed refreshes
+    pub uid: String,
+    pub created: DateTime<Utc>,
+    /// Last change, calendar apps only update their copy when this moves
+    pub modified: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub summary: String,
+    pub description: String,
+}
+
+/// Renders a calendar with the given events, `stamp` is the time the feed is generated at
+pub fn calendar(name: &str, stamp: DateTime<Utc>, events: &[Event]) -> String {
+    let mut cal = String::new();
+
+    line(&mut cal, "BEGIN:VCALENDAR");
+    line(&mut cal, "VERSION:2.0");
+    line(&mut cal, "PRODID:-//la-website//raid calendar//EN");
+    line(&mut cal, "CALSCALE:GREGORIAN");
+    line(&mut cal, "METHOD:PUBLISH");
+    line(&mut cal, &format!("X-WR-CALNAME:{}", escape(name)));
+
+    for e in events {
+        line(&mut cal, "BEGIN:VEVENT");
+        line(&mut cal, &format!("UID:{}", escape(&e.uid)));
+        line(&mut cal, &format!("DTSTAMP:{}", timestamp(&stamp)));
+        line(&mut cal, &format!("CREATED:{}", timestamp(&e.created)));
+        line(&mut cal, &format!("LAST-MODIFIED:{}", timestamp(&e.modified)));
+        line(&mut cal, &format!("DTSTART:{}", timestamp(&e.start)));
+        line(&mut cal, &format!("DTEND:{}", timestamp(&e.end)));
+        line(&mut cal, &format!("SUMMARY:{}", escape(&e.summary)));
+        line(&mut cal, &format!("DESCRIPTION:{}", escape(&e.description)));
+        line(&mut cal, "END:VEVENT");
+    }
+
+    line(&mut cal, "END:VCALENDAR");
+
+    cal
+}
+
+fn timestamp(t: &DateTime<Utc>) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes a text value
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Writes a content line, folding it without splitting multi-byte characters
+fn line(cal: &mut String, content: &str) {
+    let mut len = 0;
+
+    for c in content.chars() {
+        if len + c.len_utf8() > LINE_LIMIT {
+            cal.push_str("\r\n ");
+            // The leading space of a continuation line counts towards its length
+            len = 1;
+        }
+        cal.push(c);
+        len += c.len_utf8();
+    }
+
+    cal.push_str("\r\n");
+}
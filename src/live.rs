@@ -0,0 +1,92 @@
+//! Live updates of group pages.
+//!
+//! Every open group page holds a Server-Sent Events stream. Handlers publish a [`Change`] after
+//! their transaction is committed and the pages reload the affected parts.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web_lab::sse::{self, ChannelStream, Sse};
+use log::error;
+use serde::Serialize;
+
+use crate::repo::Repository;
+
+/// Events waiting for a slow client before it is dropped. The browser reconnects on its own.
+const CLIENT_BUFFER: usize = 16;
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    /// A raid was ticked or unticked
+    Activity { user_id: i32 },
+    /// A character was added, edited or deleted
+    Character { user_id: i32 },
+    /// Someone joined or left the group
+    Member { user_id: i32 },
+}
+
+#[derive(Default)]
+pub struct Broadcaster {
+    clients: Mutex<HashMap<i32, Vec<sse::Sender>>>,
+    closed: AtomicBool,
+}
+
+impl Broadcaster {
+    /// Opens a stream of the changes to a group
+    pub fn subscribe(&self, gid: i32) -> Sse<ChannelStream> {
+        let (tx, rx) = sse::channel(CLIENT_BUFFER);
+
+        // After shutdown started the sender is dropped right away, which ends the stream
+        if !self.closed.load(Ordering::SeqCst) {
+            self.clients.lock().unwrap().entry(gid).or_default().push(tx);
+        }
+
+        rx.with_keep_alive(Duration::from_secs(15))
+    }
+
+    /// Ends every open stream, so a graceful shutdown doesn't wait for them
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.clients.lock().unwrap().clear();
+    }
+
+    /// Sends a change to every open page of a group and forgets closed ones
+    pub fn publish(&self, gid: i32, change: Change) {
+        let data = match serde_json::to_string(&change) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("{:?}", e);
+                return;
+            },
+        };
+
+        let mut clients = self.clients.lock().unwrap();
+        let Some(senders) = clients.get_mut(&gid) else { return };
+
+        senders.retain(|s| s.try_send(sse::Data::new(data.clone()).event("change")).is_ok());
+
+        if senders.is_empty() {
+            clients.remove(&gid);
+        }
+    }
+
+    /// Sends a change of a user to every group they are a member of.
+    /// Failures are only logged, the change itself already happened.
+    pub async fn publish_for_user(&self, repo: &dyn Repository, change: Change) {
+        if self.clients.lock().unwrap().is_empty() {
+            return;
+        }
+
+        let user_id = match change {
+            Change::Activity { user_id } | Change::Character { user_id } | Change::Member { user_id } => user_id,
+        };
+
+        match repo.group_ids_of(user_id).await {
+            Ok(v) => v.into_iter().for_each(|gid| self.publish(gid, change)),
+            Err(e) => error!("{:?}", e),
+        }
+    }
+}
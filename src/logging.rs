@@ -0,0 +1,247 @@
+//! Log output and per-request context.
+//!
+//! Every request gets a random id that is sent back in the `X-Request-Id` header and shown on
+//! error pages. In the JSON format each log line is one object, and lines logged while handling a
+//! request carry its id and user, so a 500 can be traced back to the request and the errors that
+//! caused it. After each request an access line with route, status and latency is written.
+
+#[cfg(test)]
+mod tests;
+
+use std::cell::{Cell, RefCell};
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::web::Data;
+use actix_web::middleware::Logger;
+use actix_web::HttpMessage;
+use actix_web_lab::middleware::Next;
+use chrono::{SecondsFormat, Utc};
+use log::{info, Level, Record};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tera::{Context, Tera};
+
+use crate::config::LogConfig;
+use crate::crypto::random_token;
+use crate::routes::UserId;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+const ACCESS_TARGET: &str = "access";
+/// Calendar feeds are authenticated by the token in their path, which must not end up in the logs
+const CALENDAR_PREFIX: &str = "/calendar/";
+const CALENDAR_SUFFIX: &str = ".ics";
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines and the access log of `log.access_log`
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Serialize)]
+struct Access {
+    route: String,
+    status: u16,
+    latency_ms: f64,
+}
+
+struct RequestContext {
+    id: String,
+    method: String,
+    path: String,
+    user_id: Cell<Option<i32>>,
+    access: RefCell<Option<Access>>,
+    /// Messages of every error logged during the request, oldest first
+    errors: RefCell<Vec<String>>,
+}
+
+tokio::task_local! {
+    static CURRENT: Rc<RequestContext>;
+}
+
+pub fn init(config: &LogConfig) {
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::new().default_filter_or(&config.level));
+
+    if config.format == LogFormat::Json {
+        JSON.store(true, Ordering::Relaxed);
+        builder.format(|buf, record| writeln!(buf, "{}", json_line(record)));
+    }
+
+    builder.init();
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// The path of a request as it may be logged, without the secret of calendar urls
+pub fn redact_path(path: &str) -> String {
+    match path.strip_prefix(CALENDAR_PREFIX).and_then(|p| p.strip_suffix(CALENDAR_SUFFIX)) {
+        Some(token) if !token.is_empty() => format!("{}<token>{}", CALENDAR_PREFIX, CALENDAR_SUFFIX),
+        _ => path.to_string(),
+    }
+}
+
+/// The request line of `%r` with [`redact_path`] applied
+fn request_line(req: &ServiceRequest) -> String {
+    match req.query_string() {
+        "" => format!("{} {} {:?}", req.method(), redact_path(req.path()), req.version()),
+        query => format!("{} {}?{} {:?}", req.method(), redact_path(req.path()), query, req.version()),
+    }
+}
+
+/// The access log of the text format. `%r` and `%U` are replaced so calendar tokens are left out.
+pub fn access_logger(format: &str) -> Logger {
+    let format = format.replace("%r", "%{request}xi").replace("%U", "%{path}xi");
+    let mut logger = Logger::new(&format);
+
+    if format.contains("%{request}xi") {
+        logger = logger.custom_request_replace("request", request_line);
+    }
+    if format.contains("%{path}xi") {
+        logger = logger.custom_request_replace("path", |req| redact_path(req.path()));
+    }
+
+    logger
+}
+
+/// Attaches the logged in user to the log lines of the current request
+pub fn set_user_id(uid: i32) {
+    let _ = CURRENT.try_with(|c| c.user_id.set(Some(uid)));
+}
+
+fn json_line(record: &Record) -> serde_json::Value {
+    let message = record.args().to_string();
+
+    let mut line = json!({
+        "time": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": message,
+    });
+
+    let _ = CURRENT.try_with(|c| {
+        line["request_id"] = json!(c.id);
+        line["method"] = json!(c.method);
+        line["path"] = json!(c.path);
+        if let Some(uid) = c.user_id.get() {
+            line["user_id"] = json!(uid);
+        }
+
+        if record.level() == Level::Error {
+            c.errors.borrow_mut().push(message);
+        }
+
+        if record.target() == ACCESS_TARGET {
+            if let Some(access) = c.access.borrow().as_ref() {
+                line["route"] = json!(access.route);
+                line["status"] = json!(access.status);
+                line["latency_ms"] = json!(access.latency_ms);
+            }
+            let errors = c.errors.borrow();
+            if !errors.is_empty() {
+                line["errors"] = json!(*errors);
+            }
+        }
+    });
+
+    line
+}
+
+/// Gives the request an id, keeps it as context for log lines and writes the access line
+pub async fn request_context(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let id = random_token()[..16].to_string();
+
+    let context = Rc::new(RequestContext {
+        id: id.clone(),
+        method: req.method().to_string(),
+        path: redact_path(req.path()),
+        user_id: Cell::new(None),
+        access: RefCell::new(None),
+        errors: RefCell::new(Vec::new()),
+    });
+
+    CURRENT.scope(context.clone(), async move {
+        let start = Instant::now();
+        let res = next.call(req).await;
+        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        let (route, status) = match &res {
+            Ok(res) => (
+                res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string()),
+                res.status(),
+            ),
+            // Errors of middleware like the login check, the request is gone at this point
+            Err(e) => ("-".to_string(), e.as_response_error().status_code()),
+        };
+
+        if let Ok(res) = &res {
+            if let Some(UserId(uid)) = res.request().extensions().get::<UserId>() {
+                context.user_id.set(Some(*uid));
+            }
+            if let Some(e) = res.response().error() {
+                context.errors.borrow_mut().insert(0, e.to_string());
+            }
+        }
+
+        if is_json() {
+            *context.access.borrow_mut() = Some(Access { route: route.clone(), status: status.as_u16(), latency_ms });
+            info!(target: ACCESS_TARGET, "{} {} {}", context.method, route, status.as_u16());
+        }
+
+        let mut res = res?;
+        res.headers_mut().insert(REQUEST_ID_HEADER, HeaderValue::from_str(&id).expect("hex is a valid header"));
+
+        match res.status().is_server_error() {
+            true => Ok(error_page(res, &id).await),
+            false => Ok(res.map_into_boxed_body()),
+        }
+    }).await
+}
+
+/// Replaces the body of a failed request with a page that shows its id. JSON responses are kept
+/// for scripts.
+async fn error_page(res: ServiceResponse<impl MessageBody + 'static>, id: &str) -> ServiceResponse<BoxBody> {
+    let json_body = res.headers().get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let tera = match res.request().app_data::<Data<Tera>>() {
+        Some(tera) if !json_body => tera.clone(),
+        _ => return res.map_into_boxed_body(),
+    };
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+
+    let message = match body::to_bytes(body).await {
+        Ok(v) => String::from_utf8_lossy(&v).into_owned(),
+        Err(_) => String::new(),
+    };
+
+    let mut context = Context::new();
+    context.insert("message", &message);
+    context.insert("request_id", id);
+
+    let res = match tera.render("error.html", &context) {
+        Ok(page) => {
+            res.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8"));
+            res.set_body(BoxBody::new(page))
+        },
+        Err(_) => res.set_body(BoxBody::new(format!("{}\nRequest id: {}", message, id))),
+    };
+
+    ServiceResponse::new(req, res)
+}
@@ -0,0 +1,20 @@
+use actix_web::test::TestRequest;
+
+use super::{redact_path, request_line};
+
+#[test]
+fn redacts_calendar_tokens() {
+    assert_eq!(redact_path("/calendar/0123abcd.ics"), "/calendar/<token>.ics");
+    assert_eq!(redact_path("/calendar/.ics"), "/calendar/.ics");
+    assert_eq!(redact_path("/auth/me/calendar"), "/auth/me/calendar");
+    assert_eq!(redact_path("/auth/groups/3"), "/auth/groups/3");
+}
+
+#[test]
+fn request_line_leaves_out_tokens() {
+    let req = TestRequest::with_uri("/calendar/0123abcd.ics").to_srv_request();
+    assert_eq!(request_line(&req), "GET /calendar/<token>.ics HTTP/1.1");
+
+    let req = TestRequest::with_uri("/auth/groups/3/matrix?sort=available").to_srv_request();
+    assert_eq!(request_line(&req), "GET /auth/groups/3/matrix?sort=available HTTP/1.1");
+}
@@ -1,94 +1,397 @@
-use actix_session::{SessionMiddleware, storage::CookieSessionStore};
-use actix_web::{get, App, HttpServer, Responder, web::{Data, self}, middleware::Logger, cookie::Key, HttpResponse, http::header::LOCATION};
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_session::{SessionMiddleware, storage::CookieSessionStore, config::PersistentSession};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{get, App, HttpRequest, HttpServer, Responder, web::{Data, self}, middleware::Condition, cookie::{self, Key}, HttpResponse, http::header::LOCATION};
 use actix_web_lab::middleware::{from_fn, map_response};
 use actix_files::Files;
-use openssl::ssl::{SslAcceptor, SslMethod, SslFiletype};
 use routes::*;
+use cli::Command;
+use config::{Config, MetricsConfig, SessionConfig};
 use crypto::CookieSessionSecret;
-use sqlx::mysql::MySqlPoolOptions;
-use secrecy::{ExposeSecret, Secret};
-use env_logger;
+use log::{error, info, warn};
+use secrecy::ExposeSecret;
 use tera::Tera;
+use game_data::GameData;
+use live::Broadcaster;
+use metrics::Metrics;
+use proxy::TrustedProxies;
+use repo::Repository;
+use tasks::Supervisor;
+use throttle::LoginThrottle;
+use tls::CertStore;
 
+mod cli;
+mod config;
 mod data;
 mod routes;
 mod crypto;
+mod reset;
+mod armory;
+mod ical;
+mod rules;
+mod game_data;
+mod live;
+mod logging;
+mod metrics;
+mod migrate;
+mod proxy;
+mod repo;
+mod tasks;
+#[cfg(test)]
+mod testing;
+mod throttle;
+mod tls;
 
 #[get("/")]
 async fn index() -> impl Responder {
-    return HttpResponse::SeeOther()
+    HttpResponse::SeeOther()
         .insert_header((LOCATION, "/login"))
-        .finish();
+        .finish()
+}
+
+fn session_middleware(secret: &CookieSessionSecret, config: &SessionConfig, secure: bool) -> SessionMiddleware<CookieSessionStore> {
+    let builder = SessionMiddleware::builder(
+            CookieSessionStore::default(),
+            Key::from(secret.secret.expose_secret().as_bytes()))
+        .cookie_name(config.cookie_name.clone())
+        .cookie_secure(secure);
+
+    match config.max_age_days {
+        Some(days) => builder.session_lifecycle(PersistentSession::default().session_ttl(cookie::time::Duration::days(days))).build(),
+        None => builder.build(),
+    }
+}
+
+/// Everything a worker builds its [`App`] from
+#[derive(Clone)]
+struct AppState {
+    repo: Arc<dyn Repository>,
+    tera: Tera,
+    game_data: Data<GameData>,
+    live: Data<Broadcaster>,
+    proxies: Data<TrustedProxies>,
+    throttle: Data<LoginThrottle>,
+    metrics: Data<Metrics>,
+    metrics_access: Data<MetricsConfig>,
+    cookie_secret: CookieSessionSecret,
+    session: SessionConfig,
+    secure_cookies: bool,
+    access_log: String,
+    static_dir: PathBuf,
+}
+
+/// The app with all routes and middleware, as served to clients and driven by the tests
+fn app(state: &AppState) -> App<impl ServiceFactory<
+    ServiceRequest,
+    Config = (),
+    Response = ServiceResponse<impl MessageBody>,
+    Error = actix_web::Error,
+    InitError = (),
+>> {
+    App::new()
+        // Pass the repository to each handler using Actix Web's data extractor
+        .app_data(Data::from(state.repo.clone()))
+        .app_data(Data::new(state.tera.clone()))
+        .app_data(state.game_data.clone())
+        .app_data(state.live.clone())
+        .app_data(state.proxies.clone())
+        .app_data(state.throttle.clone())
+        .app_data(state.metrics.clone())
+        .app_data(state.metrics_access.clone())
+        .wrap(session_middleware(&state.cookie_secret, &state.session, state.secure_cookies))
+        .wrap({
+            let proxies = state.proxies.clone();
+            Condition::new(!logging::is_json(), logging::access_logger(&state.access_log).custom_request_replace("client", move |req| {
+                proxies.client_ip(req.peer_addr(), req.headers()).map_or("-".to_string(), |ip| ip.to_string())
+            }))
+        })
+        .wrap(from_fn(metrics::record_request))
+        .wrap(from_fn(logging::request_context))
+        .service(Files::new("/static", &state.static_dir).show_files_listing())
+        .service(base_styles)
+        .service(login_style)
+        .service(character_style)
+        .service(index)
+        .service(healthz)
+        .service(readyz)
+        .service(show_metrics)
+        .service(register)
+        .service(register_form)
+        .service(login)
+        .service(login_form)
+        .service(calendar_feed)
+        .service(web::scope("/auth")
+            .wrap(from_fn(reject_unauth_user))
+            .wrap(map_response(add_private_header))
+            .app_data(web::FormConfig::default().limit(256 * 1024))
+            .service(logout)
+            .service(show_chars)
+            .service(show_char)
+            .service(delete_char)
+            .service(delete_char_post)
+            .service(add_char)
+            .service(post_add_char)
+            .service(update_activity)
+            .service(update_task)
+            .service(edit_chars)
+            .service(edit_chars_post)
+            .service(view_groups)
+            .service(create_group)
+            .service(create_group_post)
+            .service(edit_group)
+            .service(remove_user)
+            .service(invite_group)
+            .service(invites)
+            .service(accept_invite)
+            .service(decline_invite)
+            .service(view_group)
+            .service(live_updates)
+            .service(show_matrix)
+            .service(show_availability)
+            .service(add_availability_window)
+            .service(add_availability_exception)
+            .service(delete_availability)
+            .service(create_event)
+            .service(join_event)
+            .service(leave_event)
+            .service(delete_event)
+            .service(export_roster)
+            .service(import_roster)
+            .service(import_roster_post)
+            .service(import_armory)
+            .service(import_armory_post)
+            .service(show_calendar)
+            .service(update_calendar)
+            .service(show_game_data)
+            .service(update_raid)
+            .service(update_class)
+            .service(reload_game_data)
+        )
+}
+
+/// Sends plain HTTP requests to the same url on the HTTPS port
+async fn redirect_to_https(req: HttpRequest, https_port: Data<u16>) -> HttpResponse {
+    let info = req.connection_info();
+    let host = info.host();
+    // Drop the port of the HTTP listener, unless the host is an IPv6 address without one
+    let host = match host.rsplit_once(':') {
+        Some((h, p)) if !p.contains(']') => h,
+        _ => host,
+    };
+
+    let url = match **https_port {
+        443 => format!("https://{}{}", host, req.uri()),
+        port => format!("https://{}:{}{}", host, port, req.uri()),
+    };
+
+    HttpResponse::PermanentRedirect().insert_header((LOCATION, url)).finish()
+}
+
+/// Completes on SIGTERM or SIGINT
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = term.recv() => (),
+                    _ = actix_web::rt::signal::ctrl_c() => (),
+                }
+            },
+            Err(e) => {
+                error!("Could not listen for SIGTERM: {}", e);
+                actix_web::rt::signal::ctrl_c().await.ok();
+            },
+        }
+    }
+
+    #[cfg(not(unix))]
+    actix_web::rt::signal::ctrl_c().await.ok();
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    dotenv::dotenv().expect("Unable to load environment variables from .env file");
+    let command = match Command::parse(std::env::args().skip(1)) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        },
+    };
 
-    let db_url = std::env::var("DATABASE_URL").expect("Unable to read DATABASE_URL env var");
-    let cookie_secret = CookieSessionSecret{
-        secret: Secret::new(std::env::var("COOKIE_SECRET").expect("Unable to read COOKIE_SECRET env var"))
+    if let Command::Help = command {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    let config = match Config::load() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
     };
 
-    let tera = Tera::new("templates/**/*.html").unwrap();
+    // The admin commands only need the database, the rest is checked for the server alone
+    if matches!(command, Command::Serve) {
+        if let Err(e) = config.validate_serve() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
 
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    logging::init(&config.log);
     
     // Initialize the database connection pool
-    let pool = MySqlPoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
-        .await
-        .expect("Could not connect to database");
+    let url = config.database.url.as_ref().expect("validated by Config::load").expose_secret();
+    let repo = match repo::connect(&config.database, url).await {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Could not connect to database: {}", e);
+            std::process::exit(1);
+        },
+    };
+
+    let res = match command {
+        Command::Serve => return serve(config, repo).await,
+        command => command.run(repo.as_ref(), &config).await,
+    };
 
-    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    if let Err(e) = res {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 
-    builder.set_private_key_file("key.pem", SslFiletype::PEM).unwrap();
+    Ok(())
+}
+
+async fn serve(config: Config, repo: Arc<dyn Repository>) -> std::io::Result<()> {
+    let Config { server, tls, proxy, metrics, database, session, reset, log } = config;
+
+    if database.migrate_on_start {
+        if let Err(e) = migrate::check(repo.as_ref()).await {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        if let Err(e) = migrate::up(repo.as_ref()).await {
+            error!("Could not apply migrations: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    match migrate::check(repo.as_ref()).await {
+        Ok(status) => {
+            let pending = status.iter().filter(|m| m.applied.is_none()).count();
+            if pending > 0 {
+                warn!("{} migrations are pending, run `la-website-2 migrate up` or set database.migrate_on_start", pending);
+            }
+        },
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        },
+    }
+
+    let cookie_secret = CookieSessionSecret{
+        secret: session.secret.clone().expect("validated by Config::validate_serve"),
+    };
+
+    let tera = Tera::new(&server.templates).expect("Could not load templates");
+
+    let game_data = Data::new(GameData::load(repo.as_ref()).await.expect("Could not load game data"));
+    let live = Data::new(Broadcaster::default());
 
-    builder.set_certificate_chain_file("cert.pem").unwrap();
+    let mut jobs = Supervisor::default();
+    {
+        let repo = repo.clone();
+        jobs.spawn("reset", move |shutdown| reset::reset_task(repo.clone(), reset.clone(), shutdown));
+    }
+
+    let secure_cookies = session.secure(&tls, &proxy);
+
+    let state = AppState {
+        repo,
+        tera,
+        game_data,
+        live: live.clone(),
+        proxies: Data::new(proxy.trusted),
+        throttle: Data::new(LoginThrottle::default()),
+        metrics: Data::new(Metrics::new(database.max_connections).expect("Could not register metrics")),
+        metrics_access: Data::new(metrics),
+        cookie_secret,
+        session,
+        secure_cookies,
+        access_log: log.access_log,
+        static_dir: server.static_dir.clone(),
+    };
 
     // Start the HTTP server
-    HttpServer::new(move || {
-        App::new()
-            // Pass the database connection pool to each handler using Actix Web's data extractor
-            .app_data(Data::new(pool.clone()))
-            .app_data(Data::new(tera.clone()))
-            .wrap(SessionMiddleware::new(
-                    CookieSessionStore::default(),
-                    Key::from(cookie_secret.secret.expose_secret().as_bytes())))
-            .wrap(Logger::new("%a: %r, %s"))
-            .service(Files::new("/static", "./static").show_files_listing())
-            .service(base_styles)
-            .service(login_style)
-            .service(character_style)
-            .service(index)
-            .service(register)
-            .service(register_form)
-            .service(login)
-            .service(login_form)
-            .service(web::scope("/auth")
-                .wrap(from_fn(reject_unauth_user))
-                .wrap(map_response(add_private_header))
-                .service(logout)
-                .service(show_chars)
-                .service(add_char)
-                .service(post_add_char)
-                .service(update_activity)
-                .service(edit_chars)
-                .service(edit_chars_post)
-                .service(view_groups)
-                .service(create_group)
-                .service(create_group_post)
-                .service(edit_group)
-                .service(remove_user)
-                .service(invite_group)
-                .service(invites)
-                .service(accept_invite)
-                .service(decline_invite)
-                .service(view_group)
-            )
-    })
-    .bind_openssl("0.0.0.0:8443", builder)?
-    .run()
-    .await
+    let mut http = HttpServer::new(move || app(&state));
+
+    let certs = match tls.enabled {
+        true => Some(CertStore::load(&tls).expect("Could not load the TLS key and certificate")),
+        false => None,
+    };
+
+    for addr in server.bind.iter() {
+        http = match &certs {
+            Some(certs) => http.bind_openssl(addr, certs.acceptor()?)?,
+            None => http.bind(addr)?,
+        };
+    }
+
+    if let Some(certs) = certs {
+        if tls.reload_interval > 0 {
+            let certs = certs.clone();
+            let interval = Duration::from_secs(tls.reload_interval);
+            jobs.spawn("certificate watch", move |shutdown| certs.clone().watch(interval, shutdown));
+        }
+        #[cfg(unix)]
+        jobs.spawn("certificate reload on SIGHUP", move |shutdown| certs.clone().reload_on_hangup(shutdown));
+    }
+
+    if let Some(addr) = tls.redirect_http.as_ref() {
+        let https_port = server.bind.iter()
+            .filter_map(|a| a.to_socket_addrs().ok()?.next())
+            .map(|a| a.port())
+            .next()
+            .unwrap_or(443);
+
+        let redirect = HttpServer::new(move || {
+                App::new()
+                    .app_data(Data::new(https_port))
+                    .default_service(web::to(redirect_to_https))
+            })
+            .bind(addr)?
+            .run();
+
+        actix_web::rt::spawn(redirect);
+    }
+
+    // Stops on SIGTERM or SIGINT once open requests are done, then the jobs get the same time
+    let shutdown_timeout = Duration::from_secs(server.shutdown_timeout);
+    let running = http.shutdown_timeout(server.shutdown_timeout).disable_signals().run();
+
+    let handle = running.handle();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down");
+        // Live streams never end on their own and would hold up the graceful stop
+        live.close();
+        handle.stop(true).await;
+    });
+
+    let res = running.await;
+
+    info!("Stopping background jobs");
+    jobs.shutdown(shutdown_timeout).await;
+
+    res
 }
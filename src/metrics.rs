@@ -0,0 +1,136 @@
+//! Prometheus metrics of the server, served on `/metrics` to the scrapers allowed by `[metrics]`.
+//!
+//! Request counts and latencies are recorded by [`record_request`] for every route. Pool usage is
+//! read when the metrics are scraped, the other values are updated by the handlers.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::web::Data;
+use actix_web::HttpMessage;
+use actix_web_lab::middleware::Next;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use sqlx::AnyPool;
+
+use crate::routes::UserId;
+
+/// Users with a request in this window count as an active session
+const SESSION_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    pool_connections: IntGaugeVec,
+    active_sessions: IntGauge,
+    pub activity_toggles: IntCounter,
+    /// Last request of every logged in user
+    last_seen: Mutex<HashMap<i32, Instant>>,
+}
+
+impl Metrics {
+    /// `max_connections` is the configured size limit of the database pool
+    pub fn new(max_connections: u32) -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("la".to_string()), None)?;
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )?;
+        let latency = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time to handle HTTP requests by route"),
+            &["method", "route"],
+        )?;
+        let pool_connections = IntGaugeVec::new(
+            Opts::new("db_connections", "Open database connections by state"),
+            &["state"],
+        )?;
+        let pool_max = IntGauge::new("db_max_connections", "Size limit of the database pool")?;
+        pool_max.set(max_connections as i64);
+        let active_sessions = IntGauge::new(
+            "active_sessions",
+            "Logged in users with a request in the last 15 minutes",
+        )?;
+        let activity_toggles = IntCounter::new(
+            "activity_toggles_total",
+            "Raids marked or unmarked as done, use increase(...[1h]) for toggles per hour",
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(latency.clone()))?;
+        registry.register(Box::new(pool_connections.clone()))?;
+        registry.register(Box::new(pool_max.clone()))?;
+        registry.register(Box::new(active_sessions.clone()))?;
+        registry.register(Box::new(activity_toggles.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            requests,
+            latency,
+            pool_connections,
+            active_sessions,
+            activity_toggles,
+            last_seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn seen(&self, uid: i32) {
+        self.last_seen.lock().unwrap().insert(uid, Instant::now());
+    }
+
+    /// All metrics in the Prometheus text format
+    pub fn render(&self, pool: &AnyPool) -> Result<String, prometheus::Error> {
+        let size = pool.size() as i64;
+        let idle = pool.num_idle() as i64;
+        self.pool_connections.with_label_values(&["idle"]).set(idle);
+        self.pool_connections.with_label_values(&["in_use"]).set(size - idle);
+
+        let active = {
+            let now = Instant::now();
+            let mut last_seen = self.last_seen.lock().unwrap();
+            last_seen.retain(|_, t| now - *t < SESSION_WINDOW);
+            last_seen.len()
+        };
+        self.active_sessions.set(active as i64);
+
+        let mut buf = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+
+        Ok(String::from_utf8(buf).expect("the text format is utf-8"))
+    }
+}
+
+/// Counts and times every request by its route pattern, so `/groups/1` and `/groups/2` are one route
+pub async fn record_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let metrics = req.app_data::<Data<Metrics>>().cloned();
+    let method = req.method().to_string();
+    let start = Instant::now();
+
+    let res = next.call(req).await;
+
+    let Some(metrics) = metrics else {
+        return res;
+    };
+
+    let (route, status) = match &res {
+        Ok(res) => {
+            if let Some(UserId(uid)) = res.request().extensions().get::<UserId>() {
+                metrics.seen(*uid);
+            }
+            (res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string()), res.status())
+        },
+        // Errors of middleware like the login check, the request is gone at this point
+        Err(e) => ("-".to_string(), e.as_response_error().status_code()),
+    };
+
+    metrics.requests.with_label_values(&[&method, &route, status.as_str()]).inc();
+    metrics.latency.with_label_values(&[&method, &route]).observe(start.elapsed().as_secs_f64());
+
+    res
+}
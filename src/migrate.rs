@@ -0,0 +1,124 @@
+//! Database migrations embedded into the binary.
+//!
+//! The migrations are the ones in `migrations/` for MySQL and in `migrations/postgres` and
+//! `migrations/sqlite` for the other backends. They are tracked in the `_sqlx_migrations` table,
+//! the same way `sqlx migrate run` does, so databases migrated by hand keep working.
+
+use std::collections::HashMap;
+
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::Row;
+
+use crate::repo::{Backend, Repository};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+static POSTGRES: Migrator = sqlx::migrate!("./migrations/postgres");
+static SQLITE: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// The migrations of the backend of the repository
+fn migrator(backend: Backend) -> &'static Migrator {
+    match backend {
+        Backend::MySql => &MIGRATOR,
+        Backend::Postgres => &POSTGRES,
+        Backend::Sqlite => &SQLITE,
+    }
+}
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    /// `None` if the migration was not applied yet
+    pub applied: Option<bool>,
+    /// Applied to the database, but unknown to this binary
+    pub unknown: bool,
+}
+
+/// Versions in the migration table and whether they completed successfully
+async fn applied(repo: &dyn Repository) -> Result<HashMap<i64, bool>, sqlx::Error> {
+    let pool = repo.pool();
+    let exists = match repo.backend() {
+        Backend::MySql => "SHOW TABLES LIKE '_sqlx_migrations'",
+        Backend::Postgres => "SELECT table_name FROM information_schema.tables
+            WHERE table_schema = current_schema() AND table_name = '_sqlx_migrations'",
+        Backend::Sqlite => "SELECT name FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    };
+
+    let exists = sqlx::query(exists)
+        .fetch_optional(pool)
+        .await?
+        .is_some();
+
+    if !exists {
+        return Ok(HashMap::new());
+    }
+
+    sqlx::query("SELECT version, success FROM _sqlx_migrations")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| Ok((r.try_get("version")?, r.try_get("success")?)))
+        .collect()
+}
+
+/// Every migration of the binary and the database, ordered by version
+pub async fn status(repo: &dyn Repository) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    let mut applied = applied(repo).await?;
+
+    let mut res: Vec<MigrationStatus> = migrator(repo.backend()).iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.remove(&m.version),
+            unknown: false,
+        })
+        .collect();
+
+    res.extend(applied.into_iter().map(|(version, success)| MigrationStatus {
+        version,
+        description: String::new(),
+        applied: Some(success),
+        unknown: true,
+    }));
+
+    res.sort_by_key(|m| m.version);
+
+    Ok(res)
+}
+
+/// Refuses a database that was migrated by a newer version of the server, or has a failed migration
+pub async fn check(repo: &dyn Repository) -> Result<Vec<MigrationStatus>, String> {
+    let status = status(repo).await.map_err(|e| format!("Could not read the schema version: {}", e))?;
+
+    if let Some(m) = status.iter().find(|m| m.unknown) {
+        return Err(format!(
+            "The database has migration {} which this binary does not know. It was migrated by a newer version.",
+            m.version,
+        ));
+    }
+
+    if let Some(m) = status.iter().find(|m| m.applied == Some(false)) {
+        return Err(format!("Migration {} {} failed halfway and needs to be fixed by hand", m.version, m.description));
+    }
+
+    Ok(status)
+}
+
+/// Applies all pending migrations
+pub async fn up(repo: &dyn Repository) -> Result<(), MigrateError> {
+    migrator(repo.backend()).run(repo.pool()).await
+}
+
+/// Reverts the most recent migration. Returns its version, or `None` if nothing was applied.
+pub async fn down(repo: &dyn Repository) -> Result<Option<i64>, MigrateError> {
+    let mut versions: Vec<i64> = applied(repo).await?.into_keys().collect();
+    versions.sort();
+
+    let Some(last) = versions.pop() else {
+        return Ok(None);
+    };
+
+    migrator(repo.backend()).undo(repo.pool(), versions.last().copied().unwrap_or(0)).await?;
+
+    Ok(Some(last))
+}
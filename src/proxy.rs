@@ -0,0 +1,165 @@
+//! Client addresses behind a reverse proxy.
+//!
+//! The peer of a connection is the proxy, the client is in `Forwarded` or `X-Forwarded-For`.
+//! Those headers can be sent by anyone, so they are only read when the peer is a trusted proxy,
+//! and only up to the first address that is not a trusted proxy itself.
+
+#[cfg(test)]
+mod tests;
+
+use std::future::{ready, Ready};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use actix_web::dev::Payload;
+use actix_web::http::header::HeaderMap;
+use actix_web::{web, FromRequest, HttpRequest};
+use serde::Deserialize;
+
+/// An address range like `10.0.0.0/8`, or a single address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, canonical(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            },
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            },
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((a, p)) => (a, Some(p)),
+            None => (s, None),
+        };
+
+        let addr: IpAddr = addr.trim().parse().map_err(|_| format!("{} is not an ip address", addr))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix = match prefix {
+            Some(p) => p.trim().parse::<u8>().ok().filter(|p| *p <= max).ok_or_else(|| format!("{} is not a valid prefix length", p))?,
+            None => max,
+        };
+
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct TrustedProxies(pub Vec<Cidr>);
+
+impl TrustedProxies {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|c| c.contains(ip))
+    }
+
+    /// The address of the client that sent a request through any number of trusted proxies
+    pub fn client_ip(&self, peer: Option<SocketAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let peer = canonical(peer?.ip());
+
+        if !self.trusts(peer) {
+            return Some(peer);
+        }
+
+        // Every proxy appends the address it got the request from, so the client is the last
+        // address that was not added by one of our own proxies
+        forwarded_for(headers)
+            .into_iter()
+            .rev()
+            .find(|ip| !self.trusts(*ip))
+            .or(Some(peer))
+    }
+}
+
+/// Treats IPv4 addresses mapped into IPv6 like `::ffff:10.0.0.1` as IPv4
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+/// Addresses in `Forwarded`, or in `X-Forwarded-For` if there is no `Forwarded` header
+fn forwarded_for(headers: &HeaderMap) -> Vec<IpAddr> {
+    let forwarded: Vec<IpAddr> = headers.get_all("forwarded")
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|element| element.split(';')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("for"))
+            .and_then(|(_, v)| parse_node(v)))
+        .collect();
+
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+
+    headers.get_all("x-forwarded-for")
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(parse_node)
+        .collect()
+}
+
+/// Reads `1.2.3.4`, `1.2.3.4:80`, `"[::1]:80"` and `::1`
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(canonical(ip));
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(canonical(addr.ip()));
+    }
+
+    node.strip_prefix('[')
+        .and_then(|n| n.split(']').next())
+        .and_then(|n| n.parse::<Ipv6Addr>().ok())
+        .map(|ip| canonical(IpAddr::V6(ip)))
+}
+
+/// Extracts the client address of a request, see [`TrustedProxies::client_ip`]
+pub struct ClientIp(pub IpAddr);
+
+impl FromRequest for ClientIp {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let ip = match req.app_data::<web::Data<TrustedProxies>>() {
+            Some(proxies) => proxies.client_ip(req.peer_addr(), req.headers()),
+            None => req.peer_addr().map(|a| a.ip()),
+        };
+
+        ready(Ok(ClientIp(ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)))))
+    }
+}
@@ -0,0 +1,131 @@
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+
+use super::{forwarded_for, Cidr, TrustedProxies};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn peer(s: &str) -> Option<SocketAddr> {
+    Some(SocketAddr::new(ip(s), 40000))
+}
+
+fn proxies(cidrs: &[&str]) -> TrustedProxies {
+    TrustedProxies(cidrs.iter().map(|c| c.parse().unwrap()).collect())
+}
+
+fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.append(HeaderName::from_static(name), HeaderValue::from_static(value));
+    }
+    map
+}
+
+#[test]
+fn parses_cidrs() {
+    let c: Cidr = "10.0.0.0/8".parse().unwrap();
+    assert!(c.contains(ip("10.1.2.3")));
+    assert!(!c.contains(ip("11.0.0.1")));
+
+    let single: Cidr = "192.168.1.5".parse().unwrap();
+    assert!(single.contains(ip("192.168.1.5")));
+    assert!(!single.contains(ip("192.168.1.6")));
+
+    let v6: Cidr = "fd00::/8".parse().unwrap();
+    assert!(v6.contains(ip("fd12::1")));
+    assert!(!v6.contains(ip("fe80::1")));
+
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("::/129".parse::<Cidr>().is_err());
+    assert!("10.0.0.0/x".parse::<Cidr>().is_err());
+    assert!("proxy.local".parse::<Cidr>().is_err());
+}
+
+#[test]
+fn zero_prefix_matches_everything_of_its_family() {
+    let v4: Cidr = "0.0.0.0/0".parse().unwrap();
+    assert!(v4.contains(ip("1.2.3.4")));
+    assert!(v4.contains(ip("255.255.255.255")));
+    assert!(!v4.contains(ip("2001:db8::1")));
+
+    let v6: Cidr = "::/0".parse().unwrap();
+    assert!(v6.contains(ip("2001:db8::1")));
+    assert!(!v6.contains(ip("1.2.3.4")));
+}
+
+#[test]
+fn ipv4_mapped_ipv6_is_ipv4() {
+    let c: Cidr = "10.0.0.0/8".parse().unwrap();
+    assert!(c.contains(ip("::ffff:10.0.0.1")));
+
+    let p = proxies(&["10.0.0.1"]);
+    let h = headers(&[("x-forwarded-for", "::ffff:203.0.113.7")]);
+    assert_eq!(p.client_ip(peer("::ffff:10.0.0.1"), &h), Some(ip("203.0.113.7")));
+
+    // Without a trusted proxy the peer is the client, still reported as IPv4
+    assert_eq!(TrustedProxies::default().client_ip(peer("::ffff:198.51.100.1"), &h), Some(ip("198.51.100.1")));
+}
+
+#[test]
+fn untrusted_peer_cannot_forge_headers() {
+    let p = proxies(&["10.0.0.0/8"]);
+    let h = headers(&[
+        ("x-forwarded-for", "1.1.1.1"),
+        ("forwarded", "for=2.2.2.2"),
+    ]);
+
+    assert_eq!(p.client_ip(peer("203.0.113.9"), &h), Some(ip("203.0.113.9")));
+    assert_eq!(TrustedProxies::default().client_ip(peer("10.0.0.1"), &h), Some(ip("10.0.0.1")));
+}
+
+#[test]
+fn multi_hop_chain_stops_at_first_untrusted_address() {
+    let p = proxies(&["10.0.0.0/8"]);
+
+    // The client forged the first entry, 198.51.100.2 is where our outer proxy got the request from
+    let h = headers(&[("x-forwarded-for", "1.1.1.1, 198.51.100.2, 10.0.0.5")]);
+    assert_eq!(p.client_ip(peer("10.0.0.1"), &h), Some(ip("198.51.100.2")));
+
+    // Split over several header lines
+    let h = headers(&[
+        ("x-forwarded-for", "1.1.1.1, 198.51.100.2"),
+        ("x-forwarded-for", "10.0.0.5"),
+    ]);
+    assert_eq!(p.client_ip(peer("10.0.0.1"), &h), Some(ip("198.51.100.2")));
+
+    // Only our own proxies in the chain
+    let h = headers(&[("x-forwarded-for", "10.0.0.7, 10.0.0.5")]);
+    assert_eq!(p.client_ip(peer("10.0.0.1"), &h), Some(ip("10.0.0.1")));
+}
+
+#[test]
+fn forwarded_header() {
+    let h = headers(&[("forwarded", r#"for="[2001:db8:cafe::17]:4711";proto=https, For=192.0.2.60;by=203.0.113.43"#)]);
+    assert_eq!(forwarded_for(&h), vec![ip("2001:db8:cafe::17"), ip("192.0.2.60")]);
+
+    let h = headers(&[("forwarded", r#"for="[2001:db8::1]""#), ("forwarded", "for=192.0.2.43:8080")]);
+    assert_eq!(forwarded_for(&h), vec![ip("2001:db8::1"), ip("192.0.2.43")]);
+
+    // Obfuscated and unknown nodes carry no address
+    let h = headers(&[("forwarded", "for=_hidden, for=unknown")]);
+    assert!(forwarded_for(&h).is_empty());
+}
+
+#[test]
+fn forwarded_takes_precedence_over_x_forwarded_for() {
+    let p = proxies(&["10.0.0.0/8"]);
+    let h = headers(&[
+        ("forwarded", r#"for="[2001:db8::5]:1234""#),
+        ("x-forwarded-for", "198.51.100.2"),
+    ]);
+
+    assert_eq!(p.client_ip(peer("10.0.0.1"), &h), Some(ip("2001:db8::5")));
+}
+
+#[test]
+fn no_peer_address() {
+    assert_eq!(proxies(&["10.0.0.0/8"]).client_ip(None, &HeaderMap::new()), None);
+}
@@ -0,0 +1,131 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::AnyConnection;
+
+use super::{RepoError, SqlRepo};
+use crate::data::{AvailabilityException, AvailabilityWindow};
+
+const WINDOW_COLUMNS: &str = "id, group_id, user_id, weekday, start_time, end_time, timezone";
+const EXCEPTION_COLUMNS: &str = "id, group_id, user_id, day, start_time, end_time, available, timezone";
+
+#[async_trait]
+pub trait AvailabilityRepo {
+    /// The weekly windows of every member of a group
+    async fn group_windows(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<AvailabilityWindow>, RepoError>;
+    /// The exceptions of every member of a group on or after `since`
+    async fn group_exceptions(&self, conn: &mut AnyConnection, group_id: i32, since: NaiveDate) -> Result<Vec<AvailabilityException>, RepoError>;
+    /// The weekly windows of a member, ordered by weekday and start
+    async fn member_windows(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Vec<AvailabilityWindow>, RepoError>;
+    /// The exceptions of a member on or after `since`, ordered by day and start
+    async fn member_exceptions(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, since: NaiveDate) -> Result<Vec<AvailabilityException>, RepoError>;
+    /// Stores a new window, its id is ignored
+    async fn add_window(&self, conn: &mut AnyConnection, window: &AvailabilityWindow) -> Result<(), RepoError>;
+    /// Stores a new exception, its id is ignored
+    async fn add_exception(&self, conn: &mut AnyConnection, exception: &AvailabilityException) -> Result<(), RepoError>;
+    /// Returns false if the member has no such window
+    async fn delete_window(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, id: i32) -> Result<bool, RepoError>;
+    /// Returns false if the member has no such exception
+    async fn delete_exception(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, id: i32) -> Result<bool, RepoError>;
+}
+
+#[async_trait]
+impl AvailabilityRepo for SqlRepo {
+    async fn group_windows(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<AvailabilityWindow>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT {} FROM availability_windows WHERE group_id = ?",
+            WINDOW_COLUMNS,
+        ))).bind(group_id)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn group_exceptions(&self, conn: &mut AnyConnection, group_id: i32, since: NaiveDate) -> Result<Vec<AvailabilityException>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT {} FROM availability_exceptions WHERE group_id = ? AND day >= ?",
+            EXCEPTION_COLUMNS,
+        ))).bind(group_id)
+        .bind(since)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn member_windows(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Vec<AvailabilityWindow>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT {} FROM availability_windows WHERE group_id = ? AND user_id = ? ORDER BY weekday, start_time",
+            WINDOW_COLUMNS,
+        ))).bind(group_id)
+        .bind(user_id)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn member_exceptions(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, since: NaiveDate) -> Result<Vec<AvailabilityException>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT {} FROM availability_exceptions WHERE group_id = ? AND user_id = ? AND day >= ? ORDER BY day, start_time",
+            EXCEPTION_COLUMNS,
+        ))).bind(group_id)
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn add_window(&self, conn: &mut AnyConnection, window: &AvailabilityWindow) -> Result<(), RepoError> {
+        let w = window;
+
+        sqlx::query(&self.sql(
+            "INSERT INTO availability_windows (group_id, user_id, weekday, start_time, end_time, timezone)
+            VALUES (?, ?, ?, ?, ?, ?)"
+        )).bind(w.group_id)
+        .bind(w.user_id)
+        .bind(w.weekday)
+        .bind(w.start_time)
+        .bind(w.end_time)
+        .bind(&w.timezone)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn add_exception(&self, conn: &mut AnyConnection, exception: &AvailabilityException) -> Result<(), RepoError> {
+        let e = exception;
+
+        sqlx::query(&self.sql(
+            "INSERT INTO availability_exceptions (group_id, user_id, day, start_time, end_time, available, timezone)
+            VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )).bind(e.group_id)
+        .bind(e.user_id)
+        .bind(e.day)
+        .bind(e.start_time)
+        .bind(e.end_time)
+        .bind(e.available)
+        .bind(&e.timezone)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_window(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, id: i32) -> Result<bool, RepoError> {
+        let res = sqlx::query(&self.sql("DELETE FROM availability_windows WHERE id = ? AND group_id = ? AND user_id = ?"))
+            .bind(id)
+            .bind(group_id)
+            .bind(user_id)
+            .execute(conn)
+            .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn delete_exception(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, id: i32) -> Result<bool, RepoError> {
+        let res = sqlx::query(&self.sql("DELETE FROM availability_exceptions WHERE id = ? AND group_id = ? AND user_id = ?"))
+            .bind(id)
+            .bind(group_id)
+            .bind(user_id)
+            .execute(conn)
+            .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,232 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::AnyConnection;
+
+use super::{RepoError, SqlRepo};
+use crate::data::{Character, GOLD_EARNERS};
+
+const CHARACTER_COLUMNS: &str = "id, user_id, name, class_id, item_level, gold_earner, build, stats, card_set,
+    combat_power, note, main, sort_order, version";
+
+/// A character with the name of its class, as shown on the roster pages
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CharacterOverview {
+    pub id: i32,
+    pub name: String,
+    pub class: String,
+    pub item_level: i32,
+    pub gold_earner: bool,
+}
+
+#[async_trait]
+pub trait CharacterRepo {
+    /// Adds a character at the end of the roster. It earns gold while the roster has room for it.
+    async fn add_character(&self, user_id: i32, name: &str, class_id: i32, item_level: i32) -> Result<i32, RepoError>;
+    /// Same as [`CharacterRepo::add_character`], on the transaction of the caller
+    async fn add_character_in(
+        &self,
+        conn: &mut AnyConnection,
+        user_id: i32,
+        name: &str,
+        class_id: i32,
+        item_level: i32,
+    ) -> Result<i32, RepoError>;
+    /// Returns false if the user has no such character
+    async fn delete_character(&self, user_id: i32, id: i32) -> Result<bool, RepoError>;
+    /// The roster of a user in the order they arranged it
+    async fn characters(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Character>, RepoError>;
+    /// The roster of a user, locked until the transaction ends
+    async fn characters_for_update(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Character>, RepoError>;
+    /// Stores every editable field of a character and counts up its version. With an
+    /// `expected_version` nothing is changed if someone else edited the character in the meantime.
+    /// Returns false if no character was changed.
+    async fn update_character(
+        &self,
+        conn: &mut AnyConnection,
+        character: &Character,
+        expected_version: Option<i32>,
+    ) -> Result<bool, RepoError>;
+    /// Adds a point to the item level chart of a character
+    async fn record_item_level(&self, conn: &mut AnyConnection, id: i32, item_level: i32) -> Result<(), RepoError>;
+    async fn set_sort_order(&self, conn: &mut AnyConnection, user_id: i32, id: i32, sort_order: i32) -> Result<(), RepoError>;
+    /// The roster of a user with class names, in the order they arranged it
+    async fn character_overviews(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<CharacterOverview>, RepoError>;
+    /// Returns `None` if the user has no such character
+    async fn character_overview(&self, conn: &mut AnyConnection, user_id: i32, id: i32) -> Result<Option<CharacterOverview>, RepoError>;
+    /// Recorded item levels of a character, oldest first
+    async fn item_level_history(&self, conn: &mut AnyConnection, id: i32) -> Result<Vec<(DateTime<Utc>, i32)>, RepoError>;
+}
+
+#[async_trait]
+impl CharacterRepo for SqlRepo {
+    async fn add_character(&self, user_id: i32, name: &str, class_id: i32, item_level: i32) -> Result<i32, RepoError> {
+        let mut trans = self.pool.begin().await?;
+
+        let id = self.add_character_in(&mut trans, user_id, name, class_id, item_level).await?;
+
+        trans.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn add_character_in(
+        &self,
+        conn: &mut AnyConnection,
+        user_id: i32,
+        name: &str,
+        class_id: i32,
+        item_level: i32,
+    ) -> Result<i32, RepoError> {
+        let (gold_earners, next): (i64, i32) = sqlx::query_as(&self.sql(
+            "SELECT COUNT(CASE WHEN gold_earner THEN 1 END), COALESCE(MAX(sort_order) + 1, 0)
+            FROM characters WHERE user_id = ?"
+        )).bind(user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let sql = self.returning_id(
+            "INSERT INTO characters (user_id, name, class_id, item_level, gold_earner, sort_order) VALUES (?, ?, ?, ?, ?, ?)"
+        );
+        let query = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(name)
+            .bind(class_id)
+            .bind(item_level)
+            .bind((gold_earners as usize) < GOLD_EARNERS)
+            .bind(next);
+
+        let id = self.insert_id(&mut *conn, query).await?;
+
+        self.record_item_level(conn, id, item_level).await?;
+
+        Ok(id)
+    }
+
+    async fn delete_character(&self, user_id: i32, id: i32) -> Result<bool, RepoError> {
+        let res = sqlx::query(&self.sql("DELETE FROM characters WHERE id = ? AND user_id = ?"))
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn characters(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Character>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT {} FROM characters WHERE user_id = ? ORDER BY sort_order, item_level DESC",
+            CHARACTER_COLUMNS,
+        ))).bind(user_id)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn characters_for_update(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Character>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT {} FROM characters WHERE user_id = ? ORDER BY sort_order, item_level DESC{}",
+            CHARACTER_COLUMNS,
+            self.backend.for_update(),
+        ))).bind(user_id)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn update_character(
+        &self,
+        conn: &mut AnyConnection,
+        character: &Character,
+        expected_version: Option<i32>,
+    ) -> Result<bool, RepoError> {
+        let c = character;
+        let version_check = match expected_version {
+            Some(_) => " AND version = ?",
+            None => "",
+        };
+
+        let sql = self.sql(&format!(
+            "UPDATE characters
+            SET name = ?, class_id = ?, item_level = ?, gold_earner = ?,
+                build = ?, stats = ?, card_set = ?, combat_power = ?, note = ?, main = ?,
+                version = version + 1
+            WHERE id = ? AND user_id = ?{}",
+            version_check,
+        ));
+
+        let mut query = sqlx::query(&sql)
+            .bind(&c.name)
+            .bind(c.class_id)
+            .bind(c.item_level)
+            .bind(c.gold_earner)
+            .bind(&c.build)
+            .bind(&c.stats)
+            .bind(&c.card_set)
+            .bind(c.combat_power)
+            .bind(&c.note)
+            .bind(c.main)
+            .bind(c.id)
+            .bind(c.user_id);
+
+        if let Some(version) = expected_version {
+            query = query.bind(version);
+        }
+
+        Ok(query.execute(conn).await?.rows_affected() > 0)
+    }
+
+    async fn record_item_level(&self, conn: &mut AnyConnection, id: i32, item_level: i32) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("INSERT INTO character_ilvl_history (character_id, item_level) VALUES (?, ?)"))
+            .bind(id)
+            .bind(item_level)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_sort_order(&self, conn: &mut AnyConnection, user_id: i32, id: i32, sort_order: i32) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("UPDATE characters SET sort_order = ? WHERE id = ? AND user_id = ?"))
+            .bind(sort_order)
+            .bind(id)
+            .bind(user_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn character_overviews(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<CharacterOverview>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(
+            "SELECT ch.id, ch.name, cl.name AS class, ch.item_level, ch.gold_earner
+            FROM characters ch
+            JOIN classes cl ON ch.class_id = cl.id
+            WHERE ch.user_id = ?
+            ORDER BY ch.sort_order, ch.item_level DESC"
+        )).bind(user_id)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn character_overview(&self, conn: &mut AnyConnection, user_id: i32, id: i32) -> Result<Option<CharacterOverview>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(
+            "SELECT ch.id, ch.name, cl.name AS class, ch.item_level, ch.gold_earner
+            FROM characters ch
+            JOIN classes cl ON ch.class_id = cl.id
+            WHERE ch.id = ? AND ch.user_id = ?"
+        )).bind(id)
+        .bind(user_id)
+        .fetch_optional(conn)
+        .await?)
+    }
+
+    async fn item_level_history(&self, conn: &mut AnyConnection, id: i32) -> Result<Vec<(DateTime<Utc>, i32)>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(
+            "SELECT recorded_at, item_level
+            FROM character_ilvl_history
+            WHERE character_id = ?
+            ORDER BY recorded_at, id"
+        )).bind(id)
+        .fetch_all(conn)
+        .await?)
+    }
+}
@@ -0,0 +1,181 @@
+//! A repository that counts the statements run through it, for the query count benchmarks.
+//!
+//! Every data method of the repository runs a fixed number of statements, so counting the calls
+//! shows whether a page needs more statements for more data. That works the same on every
+//! backend, unlike the statement counters of the database servers.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::pool::PoolConnection;
+use sqlx::{Any, AnyConnection, AnyPool, Transaction};
+
+use super::characters::CharacterOverview;
+use super::events::{EventCharacter, EventRecord};
+use super::groups::{GroupCharacter, GroupOverview, GroupSummary, InviteRecord, Member, PendingInvite};
+use super::users::UserRecord;
+use super::*;
+use crate::data::{AvailabilityException, AvailabilityWindow, Character, CharacterTask, Class, GroupEvent, Raid, Task};
+use crate::rules::Clear;
+
+/// Passes every call on to another repository and counts the calls that run statements
+pub struct CountingRepo {
+    inner: Arc<dyn Repository>,
+    statements: AtomicUsize,
+}
+
+impl CountingRepo {
+    pub fn new(inner: Arc<dyn Repository>) -> Self {
+        CountingRepo { inner, statements: AtomicUsize::new(0) }
+    }
+
+    /// Statements run since the repository was created
+    pub fn statements(&self) -> usize {
+        self.statements.load(Ordering::Relaxed)
+    }
+
+    fn count(&self) {
+        self.statements.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Implements a repository trait for [`CountingRepo`] by counting each call and passing it on
+macro_rules! counted {
+    ($trait:ident { $( fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty; )* }) => {
+        #[async_trait]
+        impl $trait for CountingRepo {
+            $(
+                async fn $name(&self $(, $arg: $ty)*) -> $ret {
+                    self.count();
+                    self.inner.$name($($arg),*).await
+                }
+            )*
+        }
+    };
+}
+
+counted!(UserRepo {
+    fn create_user(&self, username: &str, password_hash: &str) -> Result<i32, RepoError>;
+    fn user_by_name(&self, username: &str) -> Result<Option<UserRecord>, RepoError>;
+    fn set_password(&self, id: i32, password_hash: &str) -> Result<(), RepoError>;
+    fn set_admin(&self, id: i32, admin: bool) -> Result<(), RepoError>;
+    fn is_admin(&self, id: i32) -> Result<bool, RepoError>;
+    fn delete_user(&self, id: i32) -> Result<(), RepoError>;
+    fn username(&self, conn: &mut AnyConnection, id: i32) -> Result<String, RepoError>;
+    fn roster_level(&self, conn: &mut AnyConnection, id: i32) -> Result<i32, RepoError>;
+    fn set_roster_level(&self, conn: &mut AnyConnection, id: i32, roster_level: i32) -> Result<(), RepoError>;
+    fn calendar_token(&self, id: i32) -> Result<Option<String>, RepoError>;
+    fn set_calendar_token(&self, id: i32, token: Option<&str>) -> Result<(), RepoError>;
+    fn user_by_calendar_token(&self, conn: &mut AnyConnection, token: &str) -> Result<Option<(i32, String)>, RepoError>;
+});
+
+counted!(CharacterRepo {
+    fn add_character(&self, user_id: i32, name: &str, class_id: i32, item_level: i32) -> Result<i32, RepoError>;
+    fn add_character_in(&self, conn: &mut AnyConnection, user_id: i32, name: &str, class_id: i32, item_level: i32) -> Result<i32, RepoError>;
+    fn delete_character(&self, user_id: i32, id: i32) -> Result<bool, RepoError>;
+    fn characters(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Character>, RepoError>;
+    fn characters_for_update(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Character>, RepoError>;
+    fn update_character(&self, conn: &mut AnyConnection, character: &Character, expected_version: Option<i32>) -> Result<bool, RepoError>;
+    fn record_item_level(&self, conn: &mut AnyConnection, id: i32, item_level: i32) -> Result<(), RepoError>;
+    fn set_sort_order(&self, conn: &mut AnyConnection, user_id: i32, id: i32, sort_order: i32) -> Result<(), RepoError>;
+    fn character_overviews(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<CharacterOverview>, RepoError>;
+    fn character_overview(&self, conn: &mut AnyConnection, user_id: i32, id: i32) -> Result<Option<CharacterOverview>, RepoError>;
+    fn item_level_history(&self, conn: &mut AnyConnection, id: i32) -> Result<Vec<(DateTime<Utc>, i32)>, RepoError>;
+});
+
+counted!(RaidRepo {
+    fn raids(&self) -> Result<Vec<Raid>, RepoError>;
+    fn prerequisites(&self) -> Result<Vec<(i32, i32)>, RepoError>;
+    fn classes(&self) -> Result<Vec<Class>, RepoError>;
+    fn update_raid(&self, id: i32, required_item_level: i32, gold: i32, obsolete: bool) -> Result<(), RepoError>;
+    fn set_class_support(&self, id: i32, support: bool) -> Result<(), RepoError>;
+    fn clears_of_user(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Clear>, RepoError>;
+    fn clears_of_group(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<Clear>, RepoError>;
+    fn clears_of_character(&self, conn: &mut AnyConnection, character_id: i32) -> Result<Vec<Clear>, RepoError>;
+    fn add_clear(&self, conn: &mut AnyConnection, user_id: i32, clear: &Clear) -> Result<(), RepoError>;
+    fn merge_clear(&self, conn: &mut AnyConnection, user_id: i32, clear: &Clear) -> Result<(), RepoError>;
+    fn remove_clear(&self, conn: &mut AnyConnection, user_id: i32, character_id: i32, raid_id: i32) -> Result<(), RepoError>;
+});
+
+counted!(TaskRepo {
+    fn tasks(&self, conn: &mut AnyConnection) -> Result<Vec<Task>, RepoError>;
+    fn task_states_of_user(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<CharacterTask>, RepoError>;
+    fn task_states_of_character(&self, conn: &mut AnyConnection, character_id: i32) -> Result<Vec<CharacterTask>, RepoError>;
+    fn set_task(&self, conn: &mut AnyConnection, character_id: i32, task_id: i32, completed: bool) -> Result<(), RepoError>;
+    fn last_reset_for_update(&self, conn: &mut AnyConnection, kind: &str) -> Result<DateTime<Utc>, RepoError>;
+    fn set_last_reset(&self, conn: &mut AnyConnection, kind: &str, at: DateTime<Utc>) -> Result<(), RepoError>;
+    fn reset_daily_tasks(&self, conn: &mut AnyConnection, days: i32) -> Result<(), RepoError>;
+    fn reset_weekly(&self, conn: &mut AnyConnection) -> Result<(), RepoError>;
+});
+
+counted!(GroupRepo {
+    fn groups_of(&self, user_id: i32) -> Result<Vec<GroupSummary>, RepoError>;
+    fn group_ids_of(&self, user_id: i32) -> Result<Vec<i32>, RepoError>;
+    fn create_group(&self, name: &str, creator_id: i32) -> Result<i32, RepoError>;
+    fn owned_groups(&self, user_id: i32) -> Result<Vec<String>, RepoError>;
+    fn all_groups(&self) -> Result<Vec<GroupOverview>, RepoError>;
+    fn group_name(&self, conn: &mut AnyConnection, group_id: i32) -> Result<String, RepoError>;
+    fn group_owner(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Option<i32>, RepoError>;
+    fn member_group_name(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Option<String>, RepoError>;
+    fn is_member(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<bool, RepoError>;
+    fn members(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<Member>, RepoError>;
+    fn remove_member(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<(), RepoError>;
+    fn group_characters(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<GroupCharacter>, RepoError>;
+    fn group_view(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Option<GroupViewRecord>, RepoError>;
+    fn save_group_view(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, view: &GroupViewRecord) -> Result<(), RepoError>;
+});
+
+counted!(InviteRepo {
+    fn pending_invites(&self, user_id: i32) -> Result<Vec<PendingInvite>, RepoError>;
+    fn invite(&self, conn: &mut AnyConnection, source: i32, group_id: i32, username: &str) -> Result<(), RepoError>;
+    fn accept_invite(&self, id: i32, user_id: i32) -> Result<Option<InviteRecord>, RepoError>;
+    fn decline_invite(&self, id: i32, user_id: i32) -> Result<bool, RepoError>;
+});
+
+counted!(EventRepo {
+    fn group_events(&self, conn: &mut AnyConnection, group_id: i32, since: DateTime<Utc>) -> Result<Vec<EventRecord>, RepoError>;
+    fn group_event_characters(&self, conn: &mut AnyConnection, group_id: i32, since: DateTime<Utc>) -> Result<Vec<EventCharacter>, RepoError>;
+    fn user_events(&self, conn: &mut AnyConnection, user_id: i32, since: DateTime<Utc>) -> Result<Vec<EventRecord>, RepoError>;
+    fn user_event_characters(&self, conn: &mut AnyConnection, user_id: i32, since: DateTime<Utc>) -> Result<Vec<EventCharacter>, RepoError>;
+    fn create_event(&self, conn: &mut AnyConnection, event: &GroupEvent) -> Result<bool, RepoError>;
+    fn join_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32, character_id: i32) -> Result<(), RepoError>;
+    fn leave_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32, character_id: i32) -> Result<(), RepoError>;
+    fn delete_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32) -> Result<bool, RepoError>;
+});
+
+counted!(AvailabilityRepo {
+    fn group_windows(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<AvailabilityWindow>, RepoError>;
+    fn group_exceptions(&self, conn: &mut AnyConnection, group_id: i32, since: NaiveDate) -> Result<Vec<AvailabilityException>, RepoError>;
+    fn member_windows(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Vec<AvailabilityWindow>, RepoError>;
+    fn member_exceptions(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, since: NaiveDate) -> Result<Vec<AvailabilityException>, RepoError>;
+    fn add_window(&self, conn: &mut AnyConnection, window: &AvailabilityWindow) -> Result<(), RepoError>;
+    fn add_exception(&self, conn: &mut AnyConnection, exception: &AvailabilityException) -> Result<(), RepoError>;
+    fn delete_window(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, id: i32) -> Result<bool, RepoError>;
+    fn delete_exception(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, id: i32) -> Result<bool, RepoError>;
+});
+
+#[async_trait]
+impl Repository for CountingRepo {
+    fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    fn pool(&self) -> &AnyPool {
+        self.inner.pool()
+    }
+
+    async fn begin(&self) -> Result<Transaction<'static, Any>, RepoError> {
+        self.inner.begin().await
+    }
+
+    async fn acquire(&self) -> Result<PoolConnection<Any>, RepoError> {
+        self.inner.acquire().await
+    }
+
+    async fn stats(&self, now: DateTime<Utc>) -> Result<Stats, RepoError> {
+        self.count();
+        self.inner.stats(now).await
+    }
+}
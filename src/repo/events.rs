@@ -0,0 +1,206 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::AnyConnection;
+
+use super::{RepoError, SqlRepo};
+use crate::data::GroupEvent;
+
+/// A scheduled raid with the names of its raid and group
+#[derive(Debug, sqlx::FromRow)]
+pub struct EventRecord {
+    pub id: i32,
+    pub group_name: String,
+    pub raid: String,
+    pub difficulty: String,
+    pub starts_at: DateTime<Utc>,
+    /// In minutes
+    pub duration: i32,
+    pub note: String,
+    pub creator_id: i32,
+    pub created_at: DateTime<Utc>,
+    /// Last change of the event or its roster
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A character that joined an event
+#[derive(Debug, sqlx::FromRow)]
+pub struct EventCharacter {
+    pub event_id: i32,
+    pub id: i32,
+    pub name: String,
+    pub class: String,
+    pub item_level: i32,
+    pub user_id: i32,
+    pub username: String,
+}
+
+const GROUP_FILTER: &str = "e.group_id = ?";
+const USER_FILTER: &str = "e.group_id IN (SELECT group_id FROM group_members WHERE user_id = ?)";
+
+#[async_trait]
+pub trait EventRepo {
+    /// Events of a group starting at or after `since`, ordered by start
+    async fn group_events(&self, conn: &mut AnyConnection, group_id: i32, since: DateTime<Utc>) -> Result<Vec<EventRecord>, RepoError>;
+    /// The characters of the events of [`EventRepo::group_events`], highest item level first
+    async fn group_event_characters(&self, conn: &mut AnyConnection, group_id: i32, since: DateTime<Utc>) -> Result<Vec<EventCharacter>, RepoError>;
+    /// Events of every group of a user starting at or after `since`, ordered by start
+    async fn user_events(&self, conn: &mut AnyConnection, user_id: i32, since: DateTime<Utc>) -> Result<Vec<EventRecord>, RepoError>;
+    /// The characters of the events of [`EventRepo::user_events`], highest item level first
+    async fn user_event_characters(&self, conn: &mut AnyConnection, user_id: i32, since: DateTime<Utc>) -> Result<Vec<EventCharacter>, RepoError>;
+    /// Schedules a raid, the id and creation time of `event` are ignored. Returns false if the raid is unknown.
+    async fn create_event(&self, conn: &mut AnyConnection, event: &GroupEvent) -> Result<bool, RepoError>;
+    /// Adds a character to an event and counts as a change of it. Does nothing unless the event belongs to the group and the
+    /// character to a member of it.
+    async fn join_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32, character_id: i32) -> Result<(), RepoError>;
+    /// Removes a character from an event and counts as a change of it
+    async fn leave_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32, character_id: i32) -> Result<(), RepoError>;
+    /// Removes an event if the user created it or owns the group. Returns false otherwise.
+    async fn delete_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32) -> Result<bool, RepoError>;
+}
+
+impl SqlRepo {
+    async fn events(&self, conn: &mut AnyConnection, filter: &str, id: i32, since: DateTime<Utc>) -> Result<Vec<EventRecord>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT e.id, g.name AS group_name, r.name AS raid, r.difficulty, e.starts_at, e.duration, e.note,
+                e.creator_id, e.created_at, e.updated_at
+            FROM group_events e
+            JOIN raids r
+            ON r.id = e.raid_id
+            JOIN groups g
+            ON g.id = e.group_id
+            WHERE {} AND e.starts_at >= ?
+            ORDER BY e.starts_at",
+            filter,
+        ))).bind(id)
+        .bind(since)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    /// Marks an event as changed, so calendar apps pick up the new roster
+    async fn touch_event(&self, conn: &mut AnyConnection, event_id: i32) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("UPDATE group_events SET updated_at = ? WHERE id = ?"))
+            .bind(Utc::now())
+            .bind(event_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn event_characters(&self, conn: &mut AnyConnection, filter: &str, id: i32, since: DateTime<Utc>) -> Result<Vec<EventCharacter>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT gec.event_id, c.id, c.name, cl.name AS class, c.item_level, c.user_id, u.username
+            FROM group_event_characters gec
+            JOIN group_events e
+            ON e.id = gec.event_id
+            JOIN characters c
+            ON c.id = gec.character_id
+            JOIN classes cl
+            ON cl.id = c.class_id
+            JOIN users u
+            ON u.id = c.user_id
+            WHERE {} AND e.starts_at >= ?
+            ORDER BY c.item_level DESC",
+            filter,
+        ))).bind(id)
+        .bind(since)
+        .fetch_all(conn)
+        .await?)
+    }
+}
+
+#[async_trait]
+impl EventRepo for SqlRepo {
+    async fn group_events(&self, conn: &mut AnyConnection, group_id: i32, since: DateTime<Utc>) -> Result<Vec<EventRecord>, RepoError> {
+        self.events(conn, GROUP_FILTER, group_id, since).await
+    }
+
+    async fn group_event_characters(&self, conn: &mut AnyConnection, group_id: i32, since: DateTime<Utc>) -> Result<Vec<EventCharacter>, RepoError> {
+        self.event_characters(conn, GROUP_FILTER, group_id, since).await
+    }
+
+    async fn user_events(&self, conn: &mut AnyConnection, user_id: i32, since: DateTime<Utc>) -> Result<Vec<EventRecord>, RepoError> {
+        self.events(conn, USER_FILTER, user_id, since).await
+    }
+
+    async fn user_event_characters(&self, conn: &mut AnyConnection, user_id: i32, since: DateTime<Utc>) -> Result<Vec<EventCharacter>, RepoError> {
+        self.event_characters(conn, USER_FILTER, user_id, since).await
+    }
+
+    async fn create_event(&self, conn: &mut AnyConnection, event: &GroupEvent) -> Result<bool, RepoError> {
+        let res = sqlx::query(&self.sql(
+            "INSERT INTO group_events (group_id, raid_id, creator_id, starts_at, duration, note, updated_at)
+            SELECT ?, id, ?, ?, ?, ?, ? FROM raids WHERE id = ?"
+        )).bind(event.group_id)
+        .bind(event.creator_id)
+        .bind(event.starts_at)
+        .bind(event.duration)
+        .bind(&event.note)
+        .bind(Utc::now())
+        .bind(event.raid_id)
+        .execute(conn)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn join_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32, character_id: i32) -> Result<(), RepoError> {
+        let res = sqlx::query(&self.sql(&self.backend.insert_ignore(
+            "INSERT INTO group_event_characters (event_id, character_id)
+            SELECT e.id, c.id
+            FROM group_events e
+            JOIN group_members gm
+            ON gm.group_id = e.group_id
+            JOIN characters c
+            ON c.user_id = gm.user_id
+            WHERE e.id = ? AND e.group_id = ? AND gm.user_id = ? AND c.id = ?"
+        ))).bind(event_id)
+        .bind(group_id)
+        .bind(user_id)
+        .bind(character_id)
+        .execute(&mut *conn)
+        .await?;
+
+        if res.rows_affected() > 0 {
+            self.touch_event(conn, event_id).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn leave_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32, character_id: i32) -> Result<(), RepoError> {
+        let res = sqlx::query(&self.sql(
+            "DELETE FROM group_event_characters
+            WHERE event_id = ? AND character_id = ?
+                AND event_id IN (SELECT id FROM group_events WHERE group_id = ?)
+                AND character_id IN (SELECT id FROM characters WHERE user_id = ?)"
+        )).bind(event_id)
+        .bind(character_id)
+        .bind(group_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        if res.rows_affected() > 0 {
+            self.touch_event(conn, event_id).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn delete_event(&self, conn: &mut AnyConnection, group_id: i32, event_id: i32, user_id: i32) -> Result<bool, RepoError> {
+        let res = sqlx::query(&self.sql(
+            "DELETE FROM group_events
+            WHERE id = ? AND group_id = ?
+                AND (creator_id = ? OR group_id IN (SELECT id FROM groups WHERE creator_id = ?))"
+        )).bind(event_id)
+        .bind(group_id)
+        .bind(user_id)
+        .bind(user_id)
+        .execute(conn)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,388 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::AnyConnection;
+
+use super::{RepoError, SqlRepo};
+
+/// A group of a user with its number of members
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct GroupSummary {
+    pub id: i32,
+    pub name: String,
+    pub creator_id: i32,
+    pub amount: i64,
+}
+
+/// A group with the name of its owner, for admins
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct GroupOverview {
+    pub id: i32,
+    pub name: String,
+    pub owner: String,
+    pub members: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Member {
+    pub id: i32,
+    pub username: String,
+}
+
+/// A character of a group member, as shown in the raid matrix
+#[derive(Debug, sqlx::FromRow)]
+pub struct GroupCharacter {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub item_level: i32,
+    pub gold_earner: bool,
+    pub build: String,
+    pub stats: String,
+    pub card_set: String,
+    pub note: String,
+    pub main: bool,
+    pub combat_power: i32,
+    /// 1 if the class is a support
+    pub support: i64,
+}
+
+/// The view of the raid matrix a member saved for a group
+#[derive(Debug, Default)]
+pub struct GroupViewRecord {
+    pub build: String,
+    pub mains: bool,
+    pub member: String,
+    pub hide_obsolete: bool,
+    pub min_eligible: i32,
+    pub sort: String,
+    pub hidden_raids: Vec<i32>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PendingInvite {
+    pub id: i32,
+    /// Name of the inviting user
+    pub src: String,
+    /// Name of the group
+    pub groupn: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct InviteRecord {
+    pub group_id: i32,
+    /// The invited user
+    pub dest: i32,
+}
+
+#[async_trait]
+pub trait GroupRepo {
+    /// Groups the user is a member of
+    async fn groups_of(&self, user_id: i32) -> Result<Vec<GroupSummary>, RepoError>;
+    /// Ids of the groups the user is a member of
+    async fn group_ids_of(&self, user_id: i32) -> Result<Vec<i32>, RepoError>;
+    /// Creates a group with the creator as its only member
+    async fn create_group(&self, name: &str, creator_id: i32) -> Result<i32, RepoError>;
+    async fn owned_groups(&self, user_id: i32) -> Result<Vec<String>, RepoError>;
+    async fn all_groups(&self) -> Result<Vec<GroupOverview>, RepoError>;
+    async fn group_name(&self, conn: &mut AnyConnection, group_id: i32) -> Result<String, RepoError>;
+    /// The user that created the group, `None` if there is no such group
+    async fn group_owner(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Option<i32>, RepoError>;
+    /// The name of the group if the user is a member of it
+    async fn member_group_name(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Option<String>, RepoError>;
+    async fn is_member(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<bool, RepoError>;
+    /// Members of the group, ordered by name
+    async fn members(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<Member>, RepoError>;
+    async fn remove_member(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<(), RepoError>;
+    /// The characters of every member, each roster in the order its user arranged it
+    async fn group_characters(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<GroupCharacter>, RepoError>;
+    /// Returns `None` if the member never saved a view of the group
+    async fn group_view(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Option<GroupViewRecord>, RepoError>;
+    async fn save_group_view(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, view: &GroupViewRecord) -> Result<(), RepoError>;
+}
+
+#[async_trait]
+pub trait InviteRepo {
+    async fn pending_invites(&self, user_id: i32) -> Result<Vec<PendingInvite>, RepoError>;
+    /// Invites the user with the name into a group. Fails if there is no such user.
+    async fn invite(&self, conn: &mut AnyConnection, source: i32, group_id: i32, username: &str) -> Result<(), RepoError>;
+    /// Makes the invited user a member. Returns `None` if it is not an invite for this user.
+    async fn accept_invite(&self, id: i32, user_id: i32) -> Result<Option<InviteRecord>, RepoError>;
+    /// Returns false if it is not an invite for this user
+    async fn decline_invite(&self, id: i32, user_id: i32) -> Result<bool, RepoError>;
+}
+
+#[async_trait]
+impl GroupRepo for SqlRepo {
+    async fn groups_of(&self, user_id: i32) -> Result<Vec<GroupSummary>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(
+            "SELECT g.id, g.name, g.creator_id, COUNT(*) AS amount
+            FROM groups g
+            JOIN group_members mine ON mine.group_id = g.id AND mine.user_id = ?
+            JOIN group_members gm ON gm.group_id = g.id
+            GROUP BY g.id, g.name, g.creator_id
+            ORDER BY g.id"
+        )).bind(user_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn group_ids_of(&self, user_id: i32) -> Result<Vec<i32>, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql("SELECT group_id FROM group_members WHERE user_id = ?"))
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn create_group(&self, name: &str, creator_id: i32) -> Result<i32, RepoError> {
+        let mut trans = self.pool.begin().await?;
+
+        let sql = self.returning_id("INSERT INTO groups (name, creator_id) VALUES (?, ?)");
+        let query = sqlx::query(&sql)
+            .bind(name)
+            .bind(creator_id);
+
+        let id = self.insert_id(&mut trans, query).await?;
+
+        sqlx::query(&self.sql("INSERT INTO group_members (group_id, user_id) VALUES (?, ?)"))
+            .bind(id)
+            .bind(creator_id)
+            .execute(&mut trans)
+            .await?;
+
+        trans.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn owned_groups(&self, user_id: i32) -> Result<Vec<String>, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql("SELECT name FROM groups WHERE creator_id = ? ORDER BY id"))
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn all_groups(&self) -> Result<Vec<GroupOverview>, RepoError> {
+        Ok(sqlx::query_as(
+            "SELECT g.id, g.name, u.username AS owner, COUNT(gm.user_id) AS members
+            FROM groups g
+            JOIN users u ON u.id = g.creator_id
+            LEFT JOIN group_members gm ON gm.group_id = g.id
+            GROUP BY g.id, g.name, u.username
+            ORDER BY g.id"
+        ).fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn group_name(&self, conn: &mut AnyConnection, group_id: i32) -> Result<String, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql("SELECT name FROM groups WHERE id = ?"))
+            .bind(group_id)
+            .fetch_one(conn)
+            .await?)
+    }
+
+    async fn group_owner(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Option<i32>, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql("SELECT creator_id FROM groups WHERE id = ?"))
+            .bind(group_id)
+            .fetch_optional(conn)
+            .await?)
+    }
+
+    async fn member_group_name(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Option<String>, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql(
+            "SELECT g.name FROM groups g
+            JOIN group_members gm
+            ON g.id = gm.group_id
+            WHERE gm.user_id = ? AND g.id = ?"
+        )).bind(user_id)
+        .bind(group_id)
+        .fetch_optional(conn)
+        .await?)
+    }
+
+    async fn is_member(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<bool, RepoError> {
+        let member: Option<i32> = sqlx::query_scalar(&self.sql(
+            "SELECT user_id FROM group_members WHERE group_id = ? AND user_id = ?"
+        )).bind(group_id)
+        .bind(user_id)
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(member.is_some())
+    }
+
+    async fn members(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<Member>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(
+            "SELECT u.id, u.username
+            FROM group_members gm
+            JOIN users u
+            ON u.id = gm.user_id
+            WHERE gm.group_id = ?
+            ORDER BY u.username"
+        )).bind(group_id)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn remove_member(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("DELETE FROM group_members WHERE group_id = ? AND user_id = ?"))
+            .bind(group_id)
+            .bind(user_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn group_characters(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<GroupCharacter>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!(
+            "SELECT c.id, c.user_id, c.name, c.item_level, c.gold_earner, c.build, c.stats, c.card_set,
+                c.note, c.main, c.combat_power, {} AS support
+            FROM characters c
+            JOIN classes cl
+            ON cl.id = c.class_id
+            JOIN group_members gm
+            ON gm.user_id = c.user_id
+            WHERE gm.group_id = ?
+            ORDER BY c.sort_order, c.item_level DESC",
+            self.backend.bigint("cl.support"),
+        ))).bind(group_id)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn group_view(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32) -> Result<Option<GroupViewRecord>, RepoError> {
+        let view: Option<(String, bool, String, bool, i32, String)> = sqlx::query_as(&self.sql(
+            "SELECT build, mains, member, hide_obsolete, min_eligible, sort
+            FROM group_views
+            WHERE group_id = ? AND user_id = ?"
+        )).bind(group_id)
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let Some((build, mains, member, hide_obsolete, min_eligible, sort)) = view else {
+            return Ok(None);
+        };
+
+        let hidden_raids = sqlx::query_scalar(&self.sql(
+            "SELECT raid_id FROM group_view_hidden_raids WHERE group_id = ? AND user_id = ?"
+        )).bind(group_id)
+        .bind(user_id)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(Some(GroupViewRecord { build, mains, member, hide_obsolete, min_eligible, sort, hidden_raids }))
+    }
+
+    async fn save_group_view(&self, conn: &mut AnyConnection, group_id: i32, user_id: i32, view: &GroupViewRecord) -> Result<(), RepoError> {
+        sqlx::query(&self.sql(&self.backend.upsert(
+            "INSERT INTO group_views (group_id, user_id, build, mains, member, hide_obsolete, min_eligible, sort)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            "group_id, user_id",
+            &["build", "mains", "member", "hide_obsolete", "min_eligible", "sort"],
+        ))).bind(group_id)
+        .bind(user_id)
+        .bind(&view.build)
+        .bind(view.mains)
+        .bind(&view.member)
+        .bind(view.hide_obsolete)
+        .bind(view.min_eligible)
+        .bind(&view.sort)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(&self.sql("DELETE FROM group_view_hidden_raids WHERE group_id = ? AND user_id = ?"))
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+
+        for raid in view.hidden_raids.iter() {
+            sqlx::query(&self.sql("INSERT INTO group_view_hidden_raids (group_id, user_id, raid_id) VALUES (?, ?, ?)"))
+                .bind(group_id)
+                .bind(user_id)
+                .bind(raid)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InviteRepo for SqlRepo {
+    async fn pending_invites(&self, user_id: i32) -> Result<Vec<PendingInvite>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(
+            "SELECT i.id, u.username AS src, g.name AS groupn
+            FROM invites i
+            JOIN users u ON i.source = u.id
+            JOIN groups g ON i.group_id = g.id
+            WHERE i.dest = ?"
+        )).bind(user_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn invite(&self, conn: &mut AnyConnection, source: i32, group_id: i32, username: &str) -> Result<(), RepoError> {
+        let res = sqlx::query(&self.sql(
+            "INSERT INTO invites (source, dest, group_id) SELECT ?, id, ? FROM users WHERE username = ?"
+        )).bind(source)
+        .bind(group_id)
+        .bind(username)
+        .execute(conn)
+        .await?;
+
+        match res.rows_affected() {
+            0 => Err(RepoError::Database(sqlx::Error::RowNotFound)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn accept_invite(&self, id: i32, user_id: i32) -> Result<Option<InviteRecord>, RepoError> {
+        let mut trans = self.pool.begin().await?;
+
+        let invite: Option<InviteRecord> = sqlx::query_as(&self.sql(&format!(
+            "SELECT group_id, dest FROM invites WHERE id = ? AND dest = ?{}",
+            self.backend.for_update(),
+        ))).bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut trans)
+        .await?;
+
+        let Some(invite) = invite else {
+            return Ok(None);
+        };
+
+        sqlx::query(&self.sql(&self.backend.insert_ignore("INSERT INTO group_members (group_id, user_id) VALUES (?, ?)")))
+            .bind(invite.group_id)
+            .bind(invite.dest)
+            .execute(&mut trans)
+            .await?;
+
+        // SQLite can't lock the invite, so it may have been used while this waited for the write lock
+        let deleted = sqlx::query(&self.sql("DELETE FROM invites WHERE id = ?"))
+            .bind(id)
+            .execute(&mut trans)
+            .await?
+            .rows_affected();
+
+        if deleted == 0 {
+            return Ok(None);
+        }
+
+        trans.commit().await?;
+
+        Ok(Some(invite))
+    }
+
+    async fn decline_invite(&self, id: i32, user_id: i32) -> Result<bool, RepoError> {
+        let res = sqlx::query(&self.sql("DELETE FROM invites WHERE id = ? AND dest = ?"))
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
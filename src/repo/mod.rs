@@ -0,0 +1,311 @@
+//! Storage of users, characters, raids, groups and invites behind traits, so the server is not
+//! tied to one database.
+//!
+//! The backend is picked from the scheme of `DATABASE_URL`: `mysql://` (or `mariadb://`),
+//! `postgres://` and `sqlite://`. Every backend runs the same SQL through `sqlx::Any`, the few
+//! places where the dialects differ are written with the helpers of [`Backend`]. MySQL uses the
+//! migrations in `migrations/`, the other backends their own schema in `migrations/postgres` and
+//! `migrations/sqlite`.
+//!
+//! Methods that take a connection run on the transaction of the caller, so a handler can read and
+//! write through several of them atomically. Get one from [`Repository::begin`], or from
+//! [`Repository::acquire`] for a single read.
+
+mod availability;
+mod characters;
+#[cfg(test)]
+mod counting;
+mod events;
+mod groups;
+mod raids;
+mod tasks;
+mod users;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::any::{AnyArguments, AnyConnectOptions, AnyPoolOptions};
+use sqlx::mysql::MySqlDatabaseError;
+use sqlx::pool::PoolConnection;
+use sqlx::query::Query;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Any, AnyConnection, AnyPool, Row, Transaction};
+
+use crate::config::DatabaseConfig;
+
+pub use availability::AvailabilityRepo;
+pub use characters::CharacterRepo;
+#[cfg(test)]
+pub use counting::CountingRepo;
+pub use events::EventRepo;
+pub use groups::{GroupRepo, GroupViewRecord, InviteRepo};
+pub use raids::RaidRepo;
+pub use tasks::TaskRepo;
+pub use users::UserRepo;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    MySql,
+    Postgres,
+    Sqlite,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Result<Backend, String> {
+        match url.split_once(':').map(|(scheme, _)| scheme) {
+            Some("mysql" | "mariadb") => Ok(Backend::MySql),
+            Some("postgres" | "postgresql") => Ok(Backend::Postgres),
+            Some("sqlite") => Ok(Backend::Sqlite),
+            _ => Err("The database url must start with mysql://, postgres:// or sqlite://".to_string()),
+        }
+    }
+
+    /// Statements are written with `?` placeholders, PostgreSQL wants them numbered as `$1`, `$2`, ...
+    fn sql(&self, query: &str) -> String {
+        if *self != Backend::Postgres {
+            return query.to_string();
+        }
+
+        let mut res = String::with_capacity(query.len() + 16);
+        let mut quoted = false;
+        let mut n = 0;
+
+        for c in query.chars() {
+            match c {
+                '\'' => {
+                    quoted = !quoted;
+                    res.push(c);
+                },
+                '?' if !quoted => {
+                    n += 1;
+                    res.push('$');
+                    res.push_str(&n.to_string());
+                },
+                _ => res.push(c),
+            }
+        }
+
+        res
+    }
+
+    /// An `INSERT INTO` that skips rows whose key already exists
+    fn insert_ignore(&self, insert: &str) -> String {
+        match self {
+            Backend::MySql => insert.replacen("INSERT INTO", "INSERT IGNORE INTO", 1),
+            _ => format!("{} ON CONFLICT DO NOTHING", insert),
+        }
+    }
+
+    /// An `INSERT INTO` that overwrites `columns` of the row with the same `key` instead
+    fn upsert(&self, insert: &str, key: &str, columns: &[&str]) -> String {
+        match self {
+            Backend::MySql => {
+                let set: Vec<String> = columns.iter().map(|c| format!("{} = VALUES({})", c, c)).collect();
+                format!("{} ON DUPLICATE KEY UPDATE {}", insert, set.join(", "))
+            },
+            _ => {
+                let set: Vec<String> = columns.iter().map(|c| format!("{} = excluded.{}", c, c)).collect();
+                format!("{} ON CONFLICT ({}) DO UPDATE SET {}", insert, key, set.join(", "))
+            },
+        }
+    }
+
+    /// Locks the selected rows until the transaction ends. SQLite has no row locks, its
+    /// transactions take the whole database with their first write.
+    fn for_update(&self) -> &'static str {
+        match self {
+            Backend::Sqlite => "",
+            _ => " FOR UPDATE",
+        }
+    }
+
+    /// Reads an integer expression as `i64`. MySQL stores the class and raid flags as `BIT`.
+    fn bigint(&self, expr: &str) -> String {
+        match self {
+            Backend::MySql => format!("CAST({} AS SIGNED)", expr),
+            _ => format!("CAST({} AS BIGINT)", expr),
+        }
+    }
+
+    /// The functions returning the smaller and the greater of their arguments
+    fn least_greatest(&self) -> (&'static str, &'static str) {
+        match self {
+            Backend::Sqlite => ("MIN", "MAX"),
+            _ => ("LEAST", "GREATEST"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RepoError {
+    /// A unique value like a username is already taken
+    Duplicate,
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for RepoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoError::Duplicate => write!(f, "The entry already exists"),
+            RepoError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RepoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RepoError::Duplicate => None,
+            RepoError::Database(e) => Some(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for RepoError {
+    fn from(e: sqlx::Error) -> Self {
+        // MySQL reports foreign key and NOT NULL violations with the same SQLSTATE 23000 as
+        // duplicates, so only its own error number tells them apart (1062 is ER_DUP_ENTRY).
+        // PostgreSQL and SQLite have codes for unique violations alone.
+        let duplicate = match e.as_database_error() {
+            Some(d) => match d.try_downcast_ref::<MySqlDatabaseError>() {
+                Some(m) => m.number() == 1062,
+                None => d.code().is_some_and(|c| matches!(&*c, "23505" | "1555" | "2067")),
+            },
+            None => false,
+        };
+
+        match duplicate {
+            true => RepoError::Duplicate,
+            false => RepoError::Database(e),
+        }
+    }
+}
+
+/// Counts for the `stats` command
+#[derive(Debug, sqlx::FromRow)]
+pub struct Stats {
+    pub users: i64,
+    pub admins: i64,
+    pub characters: i64,
+    #[sqlx(rename = "groups_")]
+    pub groups: i64,
+    pub clears: i64,
+    /// Raids that did not start yet
+    pub events: i64,
+}
+
+#[async_trait]
+pub trait Repository: UserRepo + CharacterRepo + RaidRepo + TaskRepo + GroupRepo + InviteRepo + EventRepo + AvailabilityRepo + Send + Sync {
+    fn backend(&self) -> Backend;
+
+    /// The pool behind the repository, for migrations and the pool metrics
+    fn pool(&self) -> &AnyPool;
+
+    /// Starts a transaction for the methods that take a connection
+    async fn begin(&self) -> Result<Transaction<'static, Any>, RepoError>;
+
+    /// A connection for the methods that take one, when a single read needs no transaction
+    async fn acquire(&self) -> Result<PoolConnection<Any>, RepoError>;
+
+    async fn stats(&self, now: DateTime<Utc>) -> Result<Stats, RepoError>;
+}
+
+/// The repository of every backend
+pub struct SqlRepo {
+    pool: AnyPool,
+    backend: Backend,
+}
+
+impl SqlRepo {
+    pub fn new(pool: AnyPool, backend: Backend) -> Self {
+        SqlRepo { pool, backend }
+    }
+
+    pub async fn connect(config: &DatabaseConfig, url: &str, backend: Backend) -> Result<Self, sqlx::Error> {
+        let options = match backend {
+            // Foreign keys are on by default, they are needed for the cascading deletes
+            Backend::Sqlite => SqliteConnectOptions::from_str(url)?.create_if_missing(true).into(),
+            _ => AnyConnectOptions::from_str(url)?,
+        };
+
+        let pool = AnyPoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .acquire_timeout(Duration::from_secs(config.acquire_timeout))
+            .connect_with(options)
+            .await?;
+
+        Ok(SqlRepo::new(pool, backend))
+    }
+
+    fn sql(&self, query: &str) -> String {
+        self.backend.sql(query)
+    }
+
+    /// An `INSERT` of a single row that hands the new id to [`SqlRepo::insert_id`]
+    fn returning_id(&self, insert: &str) -> String {
+        match self.backend {
+            Backend::MySql => insert.to_string(),
+            _ => self.sql(&format!("{} RETURNING id", insert)),
+        }
+    }
+
+    /// Runs a statement of [`SqlRepo::returning_id`] and returns the id of the new row
+    async fn insert_id<'q>(
+        &self,
+        conn: &mut AnyConnection,
+        query: Query<'q, Any, AnyArguments<'q>>,
+    ) -> Result<i32, sqlx::Error> {
+        match self.backend {
+            Backend::MySql => Ok(query.execute(conn).await?.last_insert_id().unwrap_or_default() as i32),
+            // SQLite only finishes the statement, and with it the implicit transaction, once
+            // every row was read. `fetch_one` would stop early and keep the new row invisible.
+            _ => query.fetch_all(conn).await?.first().ok_or(sqlx::Error::RowNotFound)?.try_get(0),
+        }
+    }
+}
+
+#[async_trait]
+impl Repository for SqlRepo {
+    fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    async fn begin(&self) -> Result<Transaction<'static, Any>, RepoError> {
+        Ok(self.pool.begin().await?)
+    }
+
+    async fn acquire(&self) -> Result<PoolConnection<Any>, RepoError> {
+        Ok(self.pool.acquire().await?)
+    }
+
+    async fn stats(&self, now: DateTime<Utc>) -> Result<Stats, RepoError> {
+        Ok(sqlx::query_as(&self.sql(
+            "SELECT
+                (SELECT COUNT(*) FROM users) AS users,
+                (SELECT COUNT(*) FROM users WHERE admin) AS admins,
+                (SELECT COUNT(*) FROM characters) AS characters,
+                (SELECT COUNT(*) FROM groups) AS groups_,
+                (SELECT COUNT(*) FROM user_raids) AS clears,
+                (SELECT COUNT(*) FROM group_events WHERE starts_at > ?) AS events"
+        )).bind(now)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+}
+
+/// Connects to the database of `DATABASE_URL`
+pub async fn connect(config: &DatabaseConfig, url: &str) -> Result<Arc<dyn Repository>, String> {
+    let backend = Backend::from_url(url)?;
+    let repo = SqlRepo::connect(config, url, backend).await.map_err(|e| e.to_string())?;
+
+    Ok(Arc::new(repo))
+}
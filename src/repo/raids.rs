@@ -0,0 +1,183 @@
+use async_trait::async_trait;
+use sqlx::AnyConnection;
+
+use super::{RepoError, SqlRepo};
+use crate::data::{Class, Raid};
+use crate::rules::Clear;
+
+/// Raid as stored, the flags are selected as integers by every backend
+#[derive(sqlx::FromRow)]
+struct RaidRow {
+    id: i32,
+    name: String,
+    difficulty: String,
+    required_item_level: i32,
+    three_weekly: i64,
+    gold: i32,
+    obsolete: bool,
+}
+
+impl From<RaidRow> for Raid {
+    fn from(r: RaidRow) -> Self {
+        Raid {
+            id: r.id,
+            name: r.name,
+            difficulty: r.difficulty,
+            required_item_level: r.required_item_level,
+            three_weekly: r.three_weekly as u8,
+            gold: r.gold,
+            obsolete: r.obsolete,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ClassRow {
+    id: i32,
+    name: String,
+    support: i64,
+}
+
+impl From<ClassRow> for Class {
+    fn from(c: ClassRow) -> Self {
+        Class { id: c.id, name: c.name, support: c.support as u8 }
+    }
+}
+
+#[async_trait]
+pub trait RaidRepo {
+    /// All raids, ordered by id
+    async fn raids(&self) -> Result<Vec<Raid>, RepoError>;
+    /// Pairs of a raid and a raid that has to be cleared before it
+    async fn prerequisites(&self) -> Result<Vec<(i32, i32)>, RepoError>;
+    /// All classes, ordered by name
+    async fn classes(&self) -> Result<Vec<Class>, RepoError>;
+    async fn update_raid(&self, id: i32, required_item_level: i32, gold: i32, obsolete: bool) -> Result<(), RepoError>;
+    async fn set_class_support(&self, id: i32, support: bool) -> Result<(), RepoError>;
+    /// All clears of a users characters
+    async fn clears_of_user(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Clear>, RepoError>;
+    /// All clears of the characters of every member of a group
+    async fn clears_of_group(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<Clear>, RepoError>;
+    async fn clears_of_character(&self, conn: &mut AnyConnection, character_id: i32) -> Result<Vec<Clear>, RepoError>;
+    /// Fails if the raid is unknown or the character already cleared it
+    async fn add_clear(&self, conn: &mut AnyConnection, user_id: i32, clear: &Clear) -> Result<(), RepoError>;
+    /// Like [`RaidRepo::add_clear`], but keeps an existing clear of the raid as it is
+    async fn merge_clear(&self, conn: &mut AnyConnection, user_id: i32, clear: &Clear) -> Result<(), RepoError>;
+    async fn remove_clear(&self, conn: &mut AnyConnection, user_id: i32, character_id: i32, raid_id: i32) -> Result<(), RepoError>;
+}
+
+const INSERT_CLEAR: &str = "INSERT INTO user_raids (user_id, character_id, raid_id, took_gold) VALUES (?, ?, ?, ?)";
+
+#[async_trait]
+impl RaidRepo for SqlRepo {
+    async fn raids(&self) -> Result<Vec<Raid>, RepoError> {
+        let rows: Vec<RaidRow> = sqlx::query_as(&format!(
+            "SELECT id, name, difficulty, required_item_level, {} AS three_weekly, gold, obsolete
+            FROM raids ORDER BY id",
+            self.backend.bigint("three_weekly"),
+        )).fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Raid::from).collect())
+    }
+
+    async fn prerequisites(&self) -> Result<Vec<(i32, i32)>, RepoError> {
+        Ok(sqlx::query_as("SELECT raid, requires FROM raid_prerequisites")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn classes(&self) -> Result<Vec<Class>, RepoError> {
+        let rows: Vec<ClassRow> = sqlx::query_as(&format!(
+            "SELECT id, name, {} AS support FROM classes ORDER BY name",
+            self.backend.bigint("support"),
+        )).fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Class::from).collect())
+    }
+
+    async fn update_raid(&self, id: i32, required_item_level: i32, gold: i32, obsolete: bool) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("UPDATE raids SET required_item_level = ?, gold = ?, obsolete = ? WHERE id = ?"))
+            .bind(required_item_level)
+            .bind(gold)
+            .bind(obsolete)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_class_support(&self, id: i32, support: bool) -> Result<(), RepoError> {
+        // A SMALLINT on PostgreSQL, which doesn't take a bool
+        sqlx::query(&self.sql("UPDATE classes SET support = ? WHERE id = ?"))
+            .bind(support as i16)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn clears_of_user(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<Clear>, RepoError> {
+        Ok(sqlx::query_as(&self.sql("SELECT character_id, raid_id, took_gold FROM user_raids WHERE user_id = ?"))
+            .bind(user_id)
+            .fetch_all(conn)
+            .await?)
+    }
+
+    async fn clears_of_group(&self, conn: &mut AnyConnection, group_id: i32) -> Result<Vec<Clear>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(
+            "SELECT ur.character_id, ur.raid_id, ur.took_gold
+            FROM user_raids ur
+            JOIN group_members gm
+            ON gm.user_id = ur.user_id
+            WHERE gm.group_id = ?"
+        )).bind(group_id)
+        .fetch_all(conn)
+        .await?)
+    }
+
+    async fn clears_of_character(&self, conn: &mut AnyConnection, character_id: i32) -> Result<Vec<Clear>, RepoError> {
+        Ok(sqlx::query_as(&self.sql("SELECT character_id, raid_id, took_gold FROM user_raids WHERE character_id = ?"))
+            .bind(character_id)
+            .fetch_all(conn)
+            .await?)
+    }
+
+    async fn add_clear(&self, conn: &mut AnyConnection, user_id: i32, clear: &Clear) -> Result<(), RepoError> {
+        sqlx::query(&self.sql(INSERT_CLEAR))
+            .bind(user_id)
+            .bind(clear.character_id)
+            .bind(clear.raid_id)
+            .bind(clear.took_gold)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn merge_clear(&self, conn: &mut AnyConnection, user_id: i32, clear: &Clear) -> Result<(), RepoError> {
+        sqlx::query(&self.sql(&self.backend.insert_ignore(INSERT_CLEAR)))
+            .bind(user_id)
+            .bind(clear.character_id)
+            .bind(clear.raid_id)
+            .bind(clear.took_gold)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_clear(&self, conn: &mut AnyConnection, user_id: i32, character_id: i32, raid_id: i32) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("DELETE FROM user_raids WHERE user_id = ? AND character_id = ? AND raid_id = ?"))
+            .bind(user_id)
+            .bind(character_id)
+            .bind(raid_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+}
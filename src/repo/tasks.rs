@@ -0,0 +1,143 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::AnyConnection;
+
+use super::{RepoError, SqlRepo};
+use crate::data::{CharacterTask, Task};
+
+/// Task states of characters, including tasks a character never touched
+const TASK_STATES: &str = "SELECT c.id AS character_id, t.id AS task_id,
+        COALESCE(ct.completed, FALSE) AS completed, COALESCE(ct.rest_bonus, 0) AS rest_bonus
+    FROM characters c
+    CROSS JOIN tasks t
+    LEFT JOIN character_tasks ct
+    ON ct.character_id = c.id AND ct.task_id = t.id";
+
+#[async_trait]
+pub trait TaskRepo {
+    /// All tasks, daily ones first
+    async fn tasks(&self, conn: &mut AnyConnection) -> Result<Vec<Task>, RepoError>;
+    /// The state of every task of every character of a user, in the order of [`TaskRepo::tasks`]
+    async fn task_states_of_user(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<CharacterTask>, RepoError>;
+    /// The state of every task of a character, in the order of [`TaskRepo::tasks`]
+    async fn task_states_of_character(&self, conn: &mut AnyConnection, character_id: i32) -> Result<Vec<CharacterTask>, RepoError>;
+    async fn set_task(&self, conn: &mut AnyConnection, character_id: i32, task_id: i32, completed: bool) -> Result<(), RepoError>;
+    /// When a reset of the kind was last executed, locked until the transaction ends
+    async fn last_reset_for_update(&self, conn: &mut AnyConnection, kind: &str) -> Result<DateTime<Utc>, RepoError>;
+    async fn set_last_reset(&self, conn: &mut AnyConnection, kind: &str, at: DateTime<Utc>) -> Result<(), RepoError>;
+    /// Unticks the daily tasks of every character. Each of the `days` since the last reset grants
+    /// rest bonus, minus what a completed task used up.
+    async fn reset_daily_tasks(&self, conn: &mut AnyConnection, days: i32) -> Result<(), RepoError>;
+    /// Unticks the weekly tasks and removes all raid clears
+    async fn reset_weekly(&self, conn: &mut AnyConnection) -> Result<(), RepoError>;
+}
+
+#[async_trait]
+impl TaskRepo for SqlRepo {
+    async fn tasks(&self, conn: &mut AnyConnection) -> Result<Vec<Task>, RepoError> {
+        Ok(sqlx::query_as(
+            "SELECT id, name, reset, rest_gain, rest_cost, rest_max FROM tasks ORDER BY reset, id"
+        ).fetch_all(conn)
+        .await?)
+    }
+
+    async fn task_states_of_user(&self, conn: &mut AnyConnection, user_id: i32) -> Result<Vec<CharacterTask>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!("{} WHERE c.user_id = ? ORDER BY t.reset, t.id", TASK_STATES)))
+            .bind(user_id)
+            .fetch_all(conn)
+            .await?)
+    }
+
+    async fn task_states_of_character(&self, conn: &mut AnyConnection, character_id: i32) -> Result<Vec<CharacterTask>, RepoError> {
+        Ok(sqlx::query_as(&self.sql(&format!("{} WHERE c.id = ? ORDER BY t.reset, t.id", TASK_STATES)))
+            .bind(character_id)
+            .fetch_all(conn)
+            .await?)
+    }
+
+    async fn set_task(&self, conn: &mut AnyConnection, character_id: i32, task_id: i32, completed: bool) -> Result<(), RepoError> {
+        sqlx::query(&self.sql(&self.backend.upsert(
+            "INSERT INTO character_tasks (character_id, task_id, completed) VALUES (?, ?, ?)",
+            "character_id, task_id",
+            &["completed"],
+        ))).bind(character_id)
+        .bind(task_id)
+        .bind(completed)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn last_reset_for_update(&self, conn: &mut AnyConnection, kind: &str) -> Result<DateTime<Utc>, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql(&format!(
+            "SELECT last_reset FROM resets WHERE kind = ?{}",
+            self.backend.for_update(),
+        ))).bind(kind)
+        .fetch_one(conn)
+        .await?)
+    }
+
+    async fn set_last_reset(&self, conn: &mut AnyConnection, kind: &str, at: DateTime<Utc>) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("UPDATE resets SET last_reset = ? WHERE kind = ?"))
+            .bind(at)
+            .bind(kind)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn reset_daily_tasks(&self, conn: &mut AnyConnection, days: i32) -> Result<(), RepoError> {
+        // SQLite needs the WHERE to tell the ON CONFLICT of the insert from a join condition
+        sqlx::query(&self.backend.insert_ignore(
+            "INSERT INTO character_tasks (character_id, task_id)
+            SELECT c.id, t.id FROM characters c CROSS JOIN tasks t WHERE TRUE"
+        )).execute(&mut *conn)
+        .await?;
+
+        let (least, greatest) = self.backend.least_greatest();
+
+        // MySQL assigns from left to right, so the bonus has to be computed before the task is unticked
+        let sql = self.sql(&format!(
+            "UPDATE character_tasks
+            SET rest_bonus = CASE WHEN completed
+                    THEN {least}({greatest}(rest_bonus - ?, 0) + ? * (? - 1), ?)
+                    ELSE {least}(rest_bonus + ? * ?, ?) END,
+                completed = FALSE
+            WHERE task_id = ?",
+            least = least,
+            greatest = greatest,
+        ));
+
+        for t in self.tasks(&mut *conn).await?.into_iter().filter(|t| t.reset == "daily") {
+            sqlx::query(&sql)
+                .bind(t.rest_cost)
+                .bind(t.rest_gain)
+                .bind(days)
+                .bind(t.rest_max)
+                .bind(t.rest_gain)
+                .bind(days)
+                .bind(t.rest_max)
+                .bind(t.id)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn reset_weekly(&self, conn: &mut AnyConnection) -> Result<(), RepoError> {
+        sqlx::query(
+            "UPDATE character_tasks SET completed = FALSE
+            WHERE task_id IN (SELECT id FROM tasks WHERE reset = 'weekly')"
+        ).execute(&mut *conn)
+        .await?;
+
+        sqlx::query("DELETE FROM user_raids")
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+}
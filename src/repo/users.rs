@@ -0,0 +1,140 @@
+use async_trait::async_trait;
+use sqlx::AnyConnection;
+
+use super::{RepoError, SqlRepo};
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserRecord {
+    pub id: i32,
+    pub username: String,
+    pub password_hash: String,
+    pub admin: bool,
+}
+
+#[async_trait]
+pub trait UserRepo {
+    /// Returns the id of the new user, or [`RepoError::Duplicate`] if the name is taken
+    async fn create_user(&self, username: &str, password_hash: &str) -> Result<i32, RepoError>;
+    async fn user_by_name(&self, username: &str) -> Result<Option<UserRecord>, RepoError>;
+    async fn set_password(&self, id: i32, password_hash: &str) -> Result<(), RepoError>;
+    async fn set_admin(&self, id: i32, admin: bool) -> Result<(), RepoError>;
+    /// Whether the user may edit game data. False for unknown users.
+    async fn is_admin(&self, id: i32) -> Result<bool, RepoError>;
+    /// Deletes the user with everything they own, including their groups
+    async fn delete_user(&self, id: i32) -> Result<(), RepoError>;
+    async fn username(&self, conn: &mut AnyConnection, id: i32) -> Result<String, RepoError>;
+    async fn roster_level(&self, conn: &mut AnyConnection, id: i32) -> Result<i32, RepoError>;
+    async fn set_roster_level(&self, conn: &mut AnyConnection, id: i32, roster_level: i32) -> Result<(), RepoError>;
+    /// The secret of the calendar feed, `None` if the feed is disabled
+    async fn calendar_token(&self, id: i32) -> Result<Option<String>, RepoError>;
+    async fn set_calendar_token(&self, id: i32, token: Option<&str>) -> Result<(), RepoError>;
+    /// Id and name of the user the calendar feed belongs to
+    async fn user_by_calendar_token(&self, conn: &mut AnyConnection, token: &str) -> Result<Option<(i32, String)>, RepoError>;
+}
+
+#[async_trait]
+impl UserRepo for SqlRepo {
+    async fn create_user(&self, username: &str, password_hash: &str) -> Result<i32, RepoError> {
+        let mut conn = self.pool.acquire().await?;
+
+        let sql = self.returning_id("INSERT INTO users (username, password_hash) VALUES (?, ?)");
+        let query = sqlx::query(&sql)
+            .bind(username)
+            .bind(password_hash);
+
+        Ok(self.insert_id(&mut conn, query).await?)
+    }
+
+    async fn user_by_name(&self, username: &str) -> Result<Option<UserRecord>, RepoError> {
+        Ok(sqlx::query_as(&self.sql("SELECT id, username, password_hash, admin FROM users WHERE username = ?"))
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn set_password(&self, id: i32, password_hash: &str) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("UPDATE users SET password_hash = ? WHERE id = ?"))
+            .bind(password_hash)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_admin(&self, id: i32, admin: bool) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("UPDATE users SET admin = ? WHERE id = ?"))
+            .bind(admin)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_admin(&self, id: i32) -> Result<bool, RepoError> {
+        let admin: Option<bool> = sqlx::query_scalar(&self.sql("SELECT admin FROM users WHERE id = ?"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(admin.unwrap_or(false))
+    }
+
+    async fn delete_user(&self, id: i32) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("DELETE FROM users WHERE id = ?"))
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn username(&self, conn: &mut AnyConnection, id: i32) -> Result<String, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql("SELECT username FROM users WHERE id = ?"))
+            .bind(id)
+            .fetch_one(conn)
+            .await?)
+    }
+
+    async fn roster_level(&self, conn: &mut AnyConnection, id: i32) -> Result<i32, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql("SELECT roster_level FROM users WHERE id = ?"))
+            .bind(id)
+            .fetch_one(conn)
+            .await?)
+    }
+
+    async fn set_roster_level(&self, conn: &mut AnyConnection, id: i32, roster_level: i32) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("UPDATE users SET roster_level = ? WHERE id = ?"))
+            .bind(roster_level)
+            .bind(id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn calendar_token(&self, id: i32) -> Result<Option<String>, RepoError> {
+        Ok(sqlx::query_scalar(&self.sql("SELECT calendar_token FROM users WHERE id = ?"))
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?)
+    }
+
+    async fn set_calendar_token(&self, id: i32, token: Option<&str>) -> Result<(), RepoError> {
+        sqlx::query(&self.sql("UPDATE users SET calendar_token = ? WHERE id = ?"))
+            .bind(token)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn user_by_calendar_token(&self, conn: &mut AnyConnection, token: &str) -> Result<Option<(i32, String)>, RepoError> {
+        Ok(sqlx::query_as(&self.sql("SELECT id, username FROM users WHERE calendar_token = ?"))
+            .bind(token)
+            .fetch_optional(conn)
+            .await?)
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use log::{error, info};
+
+use crate::config::ResetConfig;
+use crate::repo::{RepoError, Repository};
+use crate::tasks::Shutdown;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetKind {
+    Daily,
+    Weekly,
+}
+
+impl ResetKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResetKind::Daily => "daily",
+            ResetKind::Weekly => "weekly",
+        }
+    }
+
+    /// The most recent reset of this kind at or before `now`
+    pub fn last_boundary(&self, now: DateTime<Utc>, schedule: &ResetConfig) -> DateTime<Utc> {
+        let today = Utc.from_utc_datetime(&now.date_naive().and_hms_opt(schedule.hour, 0, 0).unwrap());
+        let daily = if today <= now { today } else { today - Duration::days(1) };
+
+        match self {
+            ResetKind::Daily => daily,
+            ResetKind::Weekly => {
+                let days_back = (daily.weekday().num_days_from_monday() + 7
+                    - schedule.weekly_day.num_days_from_monday()) % 7;
+                daily - Duration::days(days_back as i64)
+            },
+        }
+    }
+}
+
+/// Runs every reset whose boundary has passed since it was last executed.
+pub async fn run_due_resets(repo: &dyn Repository, schedule: &ResetConfig) -> Result<(), RepoError> {
+    let now = Utc::now();
+
+    for kind in [ResetKind::Daily, ResetKind::Weekly] {
+        run_reset(repo, kind, kind.last_boundary(now, schedule), false).await?;
+    }
+
+    Ok(())
+}
+
+/// Executes a reset of the given kind if it was not yet done for `boundary`, or unconditionally
+/// if `force` is set. Returns whether the reset was executed.
+pub async fn run_reset(
+    repo: &dyn Repository,
+    kind: ResetKind,
+    boundary: DateTime<Utc>,
+    force: bool,
+) -> Result<bool, RepoError> {
+    let mut trans = repo.begin().await?;
+
+    let last = repo.last_reset_for_update(&mut trans, kind.as_str()).await?;
+
+    if !force && last >= boundary {
+        return Ok(false);
+    }
+
+    match kind {
+        ResetKind::Daily => {
+            // Every day that passed without a reset grants rest bonus
+            let days = ((boundary - last).num_hours() + 23) / 24;
+            let days = days.max(1) as i32;
+
+            repo.reset_daily_tasks(&mut trans, days).await?;
+        },
+        ResetKind::Weekly => repo.reset_weekly(&mut trans).await?,
+    }
+
+    repo.set_last_reset(&mut trans, kind.as_str(), boundary.max(last)).await?;
+
+    trans.commit().await?;
+
+    info!("Executed {} reset", kind.as_str());
+
+    Ok(true)
+}
+
+/// Periodically executes due resets until the server shuts down. A running reset is finished first.
+pub async fn reset_task(repo: Arc<dyn Repository>, schedule: ResetConfig, mut shutdown: Shutdown) {
+    let mut interval = actix_web::rt::time::interval(StdDuration::from_secs(schedule.check_interval));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            _ = shutdown.requested() => return,
+        }
+
+        if let Err(e) = run_due_resets(repo.as_ref(), &schedule).await {
+            error!("Failed to run resets: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use actix_session::Session;
+use actix_web::{get, post, web, HttpResponse, Responder, http::header};
+use log::{error, info};
+use serde::Deserialize;
+use tera::{Tera, Context};
+
+use crate::game_data::GameData;
+use crate::repo::Repository;
+
+/// Rejects users that are not admins, or returns the response for a database error
+async fn require_admin(repo: &dyn Repository, session: &Session) -> Result<(), HttpResponse> {
+    let uid = session.get::<i32>("id").unwrap().unwrap();
+
+    match repo.is_admin(uid).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(HttpResponse::Forbidden().body("Only admins can edit game data")),
+        Err(e) => {
+            error!("{:?}", e);
+            Err(HttpResponse::InternalServerError().body("Database error"))
+        },
+    }
+}
+
+/// Drops the cached game data after an edit, so every handler sees the change
+async fn reload(repo: &dyn Repository, game_data: &GameData) -> HttpResponse {
+    if let Err(e) = game_data.reload(repo).await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Database error");
+    }
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, "/auth/admin/game-data")).finish()
+}
+
+#[get("/admin/game-data")]
+async fn show_game_data(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+) -> impl Responder {
+    if let Err(res) = require_admin(repo.as_ref(), &session).await {
+        return res;
+    }
+
+    let data = game_data.get();
+
+    let mut con = Context::new();
+    con.insert("raids", &data.rules.raids);
+    con.insert("classes", &data.classes);
+
+    HttpResponse::Ok().body(tera.render("admin_game_data.html", &con).unwrap())
+}
+
+#[derive(Deserialize)]
+struct RaidForm {
+    required_item_level: i32,
+    gold: i32,
+    obsolete: Option<String>,
+}
+
+#[post("/admin/raids/{id}")]
+async fn update_raid(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    rid: web::Path<(i32,)>,
+    form: web::Form<RaidForm>,
+) -> impl Responder {
+    if let Err(res) = require_admin(repo.as_ref(), &session).await {
+        return res;
+    }
+
+    if !game_data.get().rules.raids.iter().any(|r| r.id == rid.0) {
+        return HttpResponse::NotFound().body("Unknown raid");
+    }
+
+    if form.required_item_level < 0 || form.gold < 0 {
+        return HttpResponse::BadRequest().body("Item level and gold can't be negative");
+    }
+
+    if let Err(e) = repo.update_raid(rid.0, form.required_item_level, form.gold, form.obsolete.is_some()).await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Database error");
+    }
+
+    info!("Raid {} changed by user {}", rid.0, session.get::<i32>("id").unwrap().unwrap());
+
+    reload(repo.as_ref(), &game_data).await
+}
+
+#[derive(Deserialize)]
+struct ClassForm {
+    support: Option<String>,
+}
+
+#[post("/admin/classes/{id}")]
+async fn update_class(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    cid: web::Path<(i32,)>,
+    form: web::Form<ClassForm>,
+) -> impl Responder {
+    if let Err(res) = require_admin(repo.as_ref(), &session).await {
+        return res;
+    }
+
+    if !game_data.get().classes.iter().any(|c| c.id == cid.0) {
+        return HttpResponse::NotFound().body("Unknown class");
+    }
+
+    if let Err(e) = repo.set_class_support(cid.0, form.support.is_some()).await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Database error");
+    }
+
+    info!("Class {} changed by user {}", cid.0, session.get::<i32>("id").unwrap().unwrap());
+
+    reload(repo.as_ref(), &game_data).await
+}
+
+/// Reloads the cache after the game data was changed directly in the database
+#[post("/admin/game-data/reload")]
+async fn reload_game_data(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+) -> impl Responder {
+    if let Err(res) = require_admin(repo.as_ref(), &session).await {
+        return res;
+    }
+
+    reload(repo.as_ref(), &game_data).await
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use actix_session::Session;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder, http::header};
+use chrono::{Duration, Utc};
+use log::error;
+use serde::Deserialize;
+use tera::{Tera, Context};
+
+use crate::crypto::random_token;
+use crate::ical;
+use crate::repo::Repository;
+
+/// Past events stay in the feed this long, so a running raid does not disappear
+const FEED_HISTORY_DAYS: i64 = 1;
+
+fn feed_url(req: &HttpRequest, token: &str) -> String {
+    let info = req.connection_info();
+    format!("{}://{}/calendar/{}.ics", info.scheme(), info.host(), token)
+}
+
+#[get("/me/calendar")]
+async fn show_calendar(
+    req: HttpRequest,
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let token = match repo.calendar_token(id).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let mut con = Context::new();
+    con.insert("url", &token.map(|t| feed_url(&req, &t)));
+
+    HttpResponse::Ok().body(tera.render("calendar.html", &con).unwrap())
+}
+
+#[derive(Deserialize)]
+struct CalendarForm {
+    /// `reset` creates a new secret url, `disable` removes it
+    action: String,
+}
+
+#[post("/me/calendar")]
+async fn update_calendar(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    form: web::Form<CalendarForm>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let token = match form.action.as_str() {
+        "reset" => Some(random_token()),
+        "disable" => None,
+        _ => return HttpResponse::BadRequest().body("Unknown action"),
+    };
+
+    match repo.set_calendar_token(id, token.as_deref()).await {
+        Ok(_) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, "/auth/me/calendar")).finish()
+}
+
+/// The raids of all groups of a user as an iCalendar feed. Authenticated by the secret token in the url
+/// so calendar apps can subscribe without logging in.
+#[get("/calendar/{token}.ics")]
+async fn calendar_feed(
+    req: HttpRequest,
+    repo: web::Data<dyn Repository>,
+    token: web::Path<(String,)>,
+) -> impl Responder {
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    let (uid, username) = match repo.user_by_calendar_token(&mut trans, &token.0).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return HttpResponse::NotFound().body("Unknown calendar"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let since = Utc::now() - Duration::days(FEED_HISTORY_DAYS);
+
+    let events = match repo.user_events(&mut trans, uid, since).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let mut roster: HashMap<i32, Vec<String>> = HashMap::new();
+
+    match repo.user_event_characters(&mut trans, uid, since).await {
+        Ok(v) => for c in v {
+            roster.entry(c.event_id).or_default()
+                .push(format!("{} ({}, {}) - {}", c.name, c.class, c.item_level, c.username));
+        },
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    let host = req.connection_info().host().to_string();
+
+    let events: Vec<ical::Event> = events.into_iter().map(|e| {
+        let chars = roster.remove(&e.id).unwrap_or_default();
+
+        let mut description = String::new();
+        if !e.note.is_empty() {
+            description.push_str(&e.note);
+            description.push_str("\n\n");
+        }
+        description.push_str(&format!("Roster ({}):\n{}", chars.len(), chars.join("\n")));
+
+        ical::Event {
+            uid: format!("event-{}@{}", e.id, host),
+            created: e.created_at,
+            modified: e.updated_at,
+            start: e.starts_at,
+            end: e.starts_at + Duration::minutes(e.duration as i64),
+            summary: format!("{} {} ({})", e.raid, e.difficulty, e.group_name),
+            description,
+        }
+    }).collect();
+
+    HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(ical::calendar(&format!("Raids of {}", username), Utc::now(), &events))
+}
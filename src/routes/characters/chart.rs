@@ -0,0 +1,74 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 320.0;
+const MARGIN: f64 = 40.0;
+
+/// Renders the item level progression of a character as an inline svg.
+/// `thresholds` are drawn as horizontal lines if they are inside the plotted range.
+pub(super) fn ilvl_chart(points: &[(DateTime<Utc>, i32)], thresholds: &[(String, i32)]) -> String {
+    let mut svg = String::new();
+
+    write!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" class="ilvl-chart">"#,
+        w = WIDTH, h = HEIGHT).unwrap();
+
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        svg.push_str("</svg>");
+        return svg;
+    };
+
+    let min_ilvl = points.iter().map(|p| p.1).min().unwrap() - 10;
+    let max_ilvl = points.iter().map(|p| p.1).max().unwrap() + 10;
+    let start = first.0.timestamp() as f64;
+    let span = (last.0.timestamp() as f64 - start).max(1.0);
+
+    let x = |t: &DateTime<Utc>| {
+        if points.len() == 1 {
+            WIDTH / 2.0
+        } else {
+            MARGIN + (t.timestamp() as f64 - start) / span * (WIDTH - 2.0 * MARGIN)
+        }
+    };
+    let y = |ilvl: i32| HEIGHT - MARGIN - (ilvl - min_ilvl) as f64 / (max_ilvl - min_ilvl) as f64 * (HEIGHT - 2.0 * MARGIN);
+
+    // Axes
+    write!(svg, r##"<line x1="{m}" y1="{b}" x2="{r}" y2="{b}" stroke="#555"/>"##,
+        m = MARGIN, b = HEIGHT - MARGIN, r = WIDTH - MARGIN).unwrap();
+    write!(svg, r##"<line x1="{m}" y1="{m}" x2="{m}" y2="{b}" stroke="#555"/>"##,
+        m = MARGIN, b = HEIGHT - MARGIN).unwrap();
+    write!(svg, r##"<text x="{x}" y="{y}" fill="#edefe3" font-size="12" text-anchor="end">{v}</text>"##,
+        x = MARGIN - 4.0, y = y(min_ilvl), v = min_ilvl).unwrap();
+    write!(svg, r##"<text x="{x}" y="{y}" fill="#edefe3" font-size="12" text-anchor="end">{v}</text>"##,
+        x = MARGIN - 4.0, y = y(max_ilvl), v = max_ilvl).unwrap();
+    write!(svg, r##"<text x="{x}" y="{y}" fill="#edefe3" font-size="12">{d}</text>"##,
+        x = MARGIN, y = HEIGHT - MARGIN + 16.0, d = first.0.format("%Y-%m-%d")).unwrap();
+    write!(svg, r##"<text x="{x}" y="{y}" fill="#edefe3" font-size="12" text-anchor="end">{d}</text>"##,
+        x = WIDTH - MARGIN, y = HEIGHT - MARGIN + 16.0, d = last.0.format("%Y-%m-%d")).unwrap();
+
+    for (name, ilvl) in thresholds.iter().filter(|t| t.1 > min_ilvl && t.1 < max_ilvl) {
+        write!(svg, r##"<line x1="{m}" y1="{y}" x2="{r}" y2="{y}" stroke="#e53935" stroke-dasharray="4 4"/>"##,
+            m = MARGIN, r = WIDTH - MARGIN, y = y(*ilvl)).unwrap();
+        write!(svg, r##"<text x="{x}" y="{y}" fill="#e53935" font-size="10" text-anchor="end">{n}</text>"##,
+            x = WIDTH - MARGIN, y = y(*ilvl) - 2.0, n = escape(name)).unwrap();
+    }
+
+    let line: Vec<String> = points.iter().map(|(t, ilvl)| format!("{:.1},{:.1}", x(t), y(*ilvl))).collect();
+    write!(svg, r##"<polyline points="{}" fill="none" stroke="#ffca3a" stroke-width="2"/>"##, line.join(" ")).unwrap();
+
+    for (t, ilvl) in points {
+        write!(svg, r##"<circle cx="{:.1}" cy="{:.1}" r="3" fill="#ffca3a"><title>{} ({})</title></circle>"##,
+            x(t), y(*ilvl), ilvl, t.format("%Y-%m-%d")).unwrap();
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
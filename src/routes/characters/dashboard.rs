@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::data::Raid;
+use crate::rules::GOLD_RAIDS;
+use super::CompleteChar;
+
+/// Characters at most this many item levels below a raid are listed as almost eligible
+const ALMOST_THERE: i32 = 10;
+
+#[derive(Debug, Default, Serialize)]
+pub(super) struct Dashboard {
+    gold_raids_remaining: usize,
+    gold_remaining: i32,
+    done: Vec<String>,
+    almost_eligible: Vec<AlmostEligible>,
+}
+
+#[derive(Debug, Serialize)]
+struct AlmostEligible {
+    name: String,
+    raid: String,
+    missing: i32,
+}
+
+/// Summarizes the remaining work of a roster for the current week
+pub(super) fn summarize(raids: &[Raid], chars: &[CompleteChar]) -> Dashboard {
+    let raids: HashMap<i32, &Raid> = raids.iter().map(|r| (r.id, r)).collect();
+    let mut dashboard = Dashboard::default();
+
+    for c in chars {
+        if let Some(next) = raids.values()
+            .filter(|r| r.required_item_level > c.item_level)
+            .min_by_key(|r| r.required_item_level)
+        {
+            let missing = next.required_item_level - c.item_level;
+            if missing <= ALMOST_THERE {
+                dashboard.almost_eligible.push(AlmostEligible {
+                    name: c.name.clone(),
+                    raid: format!("{} {}", next.name, next.difficulty),
+                    missing,
+                });
+            }
+        }
+
+        if !c.gold_earner {
+            continue;
+        }
+
+        let gold_clears = c.activities.iter()
+            .filter(|a| a.completed && a.took_gold && raids.get(&a.id).is_some_and(|r| r.three_weekly == 1))
+            .count();
+        let slots = GOLD_RAIDS.saturating_sub(gold_clears);
+
+        // Only the most rewarding difficulty of every raid can be done each week
+        let mut best: HashMap<&str, &Raid> = HashMap::new();
+        for a in c.activities.iter().filter(|a| a.available && !a.completed) {
+            let Some(raid) = raids.get(&a.id) else { continue };
+            let entry = best.entry(raid.name.as_str()).or_insert(raid);
+            if raid.gold > entry.gold {
+                *entry = raid;
+            }
+        }
+
+        let (mut three_weekly, continuations): (Vec<&Raid>, Vec<&Raid>) = best.into_values()
+            .partition(|r| r.three_weekly == 1);
+        three_weekly.sort_by_key(|r| -r.gold);
+        three_weekly.truncate(slots);
+
+        let remaining = three_weekly.len();
+        let gold: i32 = three_weekly.iter().chain(continuations.iter()).map(|r| r.gold).sum();
+
+        if remaining == 0 && continuations.is_empty() {
+            dashboard.done.push(c.name.clone());
+        }
+
+        dashboard.gold_raids_remaining += remaining;
+        dashboard.gold_remaining += gold;
+    }
+
+    dashboard.almost_eligible.sort_by_key(|a| a.missing);
+
+    dashboard
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::data::{Character, GOLD_EARNERS};
+
+const NAME_MIN: usize = 2;
+const NAME_MAX: usize = 16;
+const ITEM_LEVEL_MAX: i32 = 2000;
+const TEXT_MAX: usize = 255;
+const NOTE_MAX: usize = 1000;
+
+/// Checks the length of a character name
+pub(crate) fn validate_name(name: &str) -> Result<(), String> {
+    let len = name.chars().count();
+    if !(NAME_MIN..=NAME_MAX).contains(&len) {
+        return Err(format!("Name must be between {} and {} characters", NAME_MIN, NAME_MAX));
+    }
+    Ok(())
+}
+
+/// Checks that an item level is inside the range the game allows
+pub(crate) fn validate_item_level(item_level: i32) -> Result<i32, String> {
+    if !(0..=ITEM_LEVEL_MAX).contains(&item_level) {
+        return Err(format!("Item level must be a number between 0 and {}", ITEM_LEVEL_MAX));
+    }
+    Ok(item_level)
+}
+
+/// Parses an item level as it was typed into a form
+pub(crate) fn parse_item_level(item_level: &str) -> Result<i32, String> {
+    match item_level.parse::<i32>() {
+        Ok(v) => validate_item_level(v),
+        Err(_) => Err("Item level must be a number".to_string()),
+    }
+}
+
+/// Checks that a combat power is not negative
+pub(crate) fn validate_combat_power(combat_power: i32) -> Result<i32, String> {
+    if combat_power < 0 {
+        return Err("Combat power must be a positive number".to_string());
+    }
+    Ok(combat_power)
+}
+
+/// Parses a combat power as it was typed into a form, an empty field counts as 0
+pub(crate) fn parse_combat_power(combat_power: &str) -> Result<i32, String> {
+    match combat_power {
+        "" => Ok(0),
+        v => v.parse::<i32>()
+            .map_err(|_| "Combat power must be a positive number".to_string())
+            .and_then(validate_combat_power),
+    }
+}
+
+/// Checks the free text fields of a character against the sizes of their columns
+pub(crate) fn validate_texts(build: &str, stats: &str, card_set: &str, note: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for (name, value) in [("Build", build), ("Stats", stats), ("Card set", card_set)] {
+        if value.chars().count() > TEXT_MAX {
+            errors.push(format!("{} can be at most {} characters", name, TEXT_MAX));
+        }
+    }
+
+    if note.chars().count() > NOTE_MAX {
+        errors.push(format!("Note can be at most {} characters", NOTE_MAX));
+    }
+
+    errors
+}
+
+/// Every field a row of the bulk editor has to submit, as `field[character id]`
+const FIELDS: [&str; 11] = [
+    "version", "name", "class_id", "item_level", "gold_earner", "build",
+    "stats", "card_set", "combat_power", "note", "main",
+];
+
+/// A row of the bulk editor as it was submitted, so it can be shown again with its errors
+#[derive(Debug, Default, Serialize)]
+pub(super) struct CharRow {
+    pub id: i32,
+    pub version: i32,
+    pub name: String,
+    pub class_id: i32,
+    pub item_level: String,
+    pub gold_earner: bool,
+    pub build: String,
+    pub stats: String,
+    pub card_set: String,
+    pub combat_power: String,
+    pub note: String,
+    pub main: bool,
+    pub errors: Vec<String>,
+}
+
+/// A row that passed validation
+#[derive(Debug)]
+pub(super) struct ValidRow {
+    pub id: i32,
+    pub version: i32,
+    pub name: String,
+    pub class_id: i32,
+    pub item_level: i32,
+    pub gold_earner: bool,
+    pub build: String,
+    pub stats: String,
+    pub card_set: String,
+    pub combat_power: i32,
+    pub note: String,
+    pub main: bool,
+}
+
+impl ValidRow {
+    /// Whether saving this row would change the stored character
+    pub fn differs(&self, c: &Character) -> bool {
+        self.name != c.name || self.class_id != c.class_id || self.item_level != c.item_level
+            || self.gold_earner != c.gold_earner || self.build != c.build || self.stats != c.stats
+            || self.card_set != c.card_set || self.combat_power != c.combat_power
+            || self.note != c.note || self.main != c.main
+    }
+
+    /// The character this row saves, its position is stored separately
+    pub fn to_character(&self, user_id: i32) -> Character {
+        Character {
+            id: self.id,
+            user_id,
+            name: self.name.clone(),
+            class_id: self.class_id,
+            item_level: self.item_level,
+            gold_earner: self.gold_earner,
+            build: self.build.clone(),
+            stats: self.stats.clone(),
+            card_set: self.card_set.clone(),
+            combat_power: self.combat_power,
+            note: self.note.clone(),
+            main: self.main,
+            sort_order: 0,
+            version: self.version,
+        }
+    }
+}
+
+impl From<&Character> for CharRow {
+    fn from(c: &Character) -> Self {
+        CharRow {
+            id: c.id,
+            version: c.version,
+            name: c.name.clone(),
+            class_id: c.class_id,
+            item_level: c.item_level.to_string(),
+            gold_earner: c.gold_earner,
+            build: c.build.clone(),
+            stats: c.stats.clone(),
+            card_set: c.card_set.clone(),
+            combat_power: c.combat_power.to_string(),
+            note: c.note.clone(),
+            main: c.main,
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// The submitted bulk editor, rows in the order the user arranged them in
+#[derive(Debug, Default, Serialize)]
+pub(super) struct EditForm {
+    pub rows: Vec<CharRow>,
+    pub roster_level: String,
+    pub errors: Vec<String>,
+}
+
+impl EditForm {
+    /// Groups the submitted `field[id]` pairs into rows. Fails if the form is malformed,
+    /// which does not happen with the page we render.
+    pub fn parse(pairs: Vec<(String, String)>) -> Result<Self, String> {
+        let mut order = Vec::new();
+        let mut raw: HashMap<i32, HashMap<&'static str, String>> = HashMap::new();
+        let mut form = EditForm::default();
+
+        for (k, v) in pairs {
+            if k == "roster_level" {
+                form.roster_level = v;
+                continue;
+            }
+
+            if k == "cid[]" {
+                order.push(v.parse::<i32>().map_err(|_| format!("Invalid character id {}", v))?);
+                continue;
+            }
+
+            let (field, id) = k.strip_suffix(']')
+                .and_then(|k| k.split_once('['))
+                .ok_or_else(|| format!("Invalid field {}", k))?;
+            let field = FIELDS.iter().find(|f| **f == field).ok_or_else(|| format!("Invalid field {}", k))?;
+            let id = id.parse::<i32>().map_err(|_| format!("Invalid field {}", k))?;
+
+            raw.entry(id).or_default().insert(*field, v);
+        }
+
+        for id in order {
+            let Some(mut fields) = raw.remove(&id) else {
+                return Err(format!("Missing data for character {}", id));
+            };
+
+            let mut take = |f: &str| fields.remove(f).ok_or_else(|| format!("Missing {} for character {}", f, id));
+
+            form.rows.push(CharRow {
+                id,
+                version: take("version")?.parse().map_err(|_| "Invalid version".to_string())?,
+                name: take("name")?.trim().to_string(),
+                class_id: take("class_id")?.parse().map_err(|_| "Invalid class".to_string())?,
+                item_level: take("item_level")?.trim().to_string(),
+                gold_earner: take("gold_earner")?.parse().map_err(|_| "Invalid gold flag".to_string())?,
+                build: take("build")?.trim().to_string(),
+                stats: take("stats")?.trim().to_string(),
+                card_set: take("card_set")?.trim().to_string(),
+                combat_power: take("combat_power")?.trim().to_string(),
+                note: take("note")?,
+                main: take("main")?.parse().map_err(|_| "Invalid main flag".to_string())?,
+                errors: Vec::new(),
+            });
+        }
+
+        if !raw.is_empty() {
+            return Err("Data for characters that are not part of the form".to_string());
+        }
+
+        Ok(form)
+    }
+
+    /// Checks every row and records the problems on the rows themselves.
+    /// Returns the validated rows if there were no errors.
+    pub fn validate(&mut self, class_ids: &[i32]) -> Option<(Vec<ValidRow>, Option<i32>)> {
+        let mut valid = Vec::new();
+
+        for row in self.rows.iter_mut() {
+            if let Err(e) = validate_name(&row.name) {
+                row.errors.push(e);
+            }
+
+            if !class_ids.contains(&row.class_id) {
+                row.errors.push("Unknown class".to_string());
+            }
+
+            let item_level = parse_item_level(&row.item_level).unwrap_or_else(|e| {
+                row.errors.push(e);
+                0
+            });
+
+            let combat_power = parse_combat_power(&row.combat_power).unwrap_or_else(|e| {
+                row.errors.push(e);
+                0
+            });
+
+            let texts = validate_texts(&row.build, &row.stats, &row.card_set, &row.note);
+            row.errors.extend(texts);
+
+            valid.push(ValidRow {
+                id: row.id,
+                version: row.version,
+                name: row.name.clone(),
+                class_id: row.class_id,
+                item_level,
+                gold_earner: row.gold_earner,
+                build: row.build.clone(),
+                stats: row.stats.clone(),
+                card_set: row.card_set.clone(),
+                combat_power,
+                note: row.note.clone(),
+                main: row.main,
+            });
+        }
+
+        if self.rows.iter().filter(|r| r.gold_earner).count() > GOLD_EARNERS {
+            self.errors.push(format!("Only {} characters can earn gold each week", GOLD_EARNERS));
+        }
+
+        if self.rows.iter().filter(|r| r.main).count() > 1 {
+            self.errors.push("Only one character can be your main".to_string());
+        }
+
+        let roster_level = match self.roster_level.trim() {
+            "" => None,
+            v => match v.parse::<i32>() {
+                Ok(v) if v >= 0 => Some(v),
+                _ => {
+                    self.errors.push("Roster level must be a positive number".to_string());
+                    None
+                },
+            },
+        };
+
+        if self.errors.is_empty() && self.rows.iter().all(|r| r.errors.is_empty()) {
+            Some((valid, roster_level))
+        } else {
+            None
+        }
+    }
+
+    pub fn row_mut(&mut self, id: i32) -> Option<&mut CharRow> {
+        self.rows.iter_mut().find(|r| r.id == id)
+    }
+}
@@ -1,27 +1,36 @@
-use crate::data::{Character, Class};
-
-use actix_web::{get, post, Responder, HttpResponse, http::header::LOCATION};
+mod chart;
+mod dashboard;
+mod edit;
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+
+use crate::data::{Character, Class, Task};
+use crate::game_data::GameData;
+use crate::live::{Broadcaster, Change};
+use crate::metrics::Metrics;
+use crate::repo::{RepoError, Repository};
+use crate::rules::{self, Clear, RaidRules};
+use dashboard::Dashboard;
+use edit::{CharRow, EditForm};
+pub(crate) use edit::{validate_combat_power, validate_item_level, validate_name, validate_texts};
+
+use actix_web::{get, post, Responder, HttpResponse, HttpResponseBuilder, http::header::LOCATION};
 use actix_session::Session;
 use actix_web::web;
-use log::error;
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
-use sqlx::{MySqlPool, query_as};
+use sqlx::AnyConnection;
 use tera::{Tera, Context};
-use itertools::izip;
 
 #[derive(Debug, Serialize)]
-struct CharContext {
+pub(crate) struct CharContext {
     activities: Vec<Activity>,
+    tasks: Vec<Task>,
     chars: Vec<CompleteChar>,
     name: String,
-}
-
-#[derive(Serialize)]
-struct RenderableChar {
-    id: i32,
-    name: String,
-    class: String,
-    item_level: i32,
+    dashboard: Dashboard,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -30,6 +39,7 @@ struct Activity {
     name: String,
     difficulty: String,
     completed: bool,
+    took_gold: bool,
     available: bool,
 }
 
@@ -39,7 +49,23 @@ struct CompleteChar {
     name: String,
     class: String,
     item_level: i32,
+    gold_earner: bool,
     activities: Vec<Activity>,
+    tasks: Vec<TaskState>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct TaskState {
+    id: i32,
+    completed: bool,
+    rest_bonus: i32,
+}
+
+#[derive(Deserialize, Debug)]
+struct TaskUpdate {
+    character_id: i32,
+    task_id: i32,
+    completed: bool,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -47,411 +73,569 @@ struct ActivityUpdate {
     character_id: i32,
     activity_id: i32,
     completed: bool,
+    /// Set to false to record a bus/helper run; defaults to the characters gold earner flag
+    took_gold: Option<bool>,
 }
 
 #[derive(Serialize)]
 struct RaidAvailable {
     id: i32,
-    completed: i32,
-    available: Option<i32>,
-}
-
-#[derive(Deserialize, Debug, Default)]
-struct CharUpdate {
-    cuid: Vec<i32>,
-    cid: Vec<i32>,
-    cname: Vec<String>,
-    oldcname: Vec<String>,
-    cclass_id: Vec<i32>,
-    oldcclass_id: Vec<i32>,
-    item_level: Vec<i32>,
-    olditem_level: Vec<i32>,
+    completed: bool,
+    took_gold: bool,
+    available: bool,
 }
 
 #[post("/me/edit_chars")]
 async fn edit_chars_post(
+    tera: web::Data<Tera>,
     session: Session,
-    pool: web::Data<MySqlPool>,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    live: web::Data<Broadcaster>,
     form: web::Form<Vec<(String, String)>>
 ) -> impl Responder {
-    let mut update = CharUpdate::default();
-
-    for (k, v) in &form.into_inner() {
-
-        if let Err(_) = match k.as_str() {
-            "cuid[]" => { if let Ok(v) = v.parse(){ update.cuid.push(v); Ok(()) } else { Err(()) }},
-            "cid[]" => { if let Ok(v) = v.parse(){ update.cid.push(v); Ok(()) } else { Err(()) }},
-            "cname[]" => { update.cname.push(v.clone()); Ok(()) },
-            "oldcname[]" => { update.oldcname.push(v.clone()); Ok(()) },
-            "cclass_id[]" => { if let Ok(v) = v.parse(){ update.cclass_id.push(v); Ok(()) } else { Err(()) }},
-            "oldcclass_id[]" => { if let Ok(v) = v.parse(){ update.oldcclass_id.push(v); Ok(()) } else { Err(()) }},
-            "item_level[]" => { if let Ok(v) = v.parse(){ update.item_level.push(v); Ok(()) } else { Err(()) }},
-            "olditem_level[]" => { if let Ok(v) = v.parse(){ update.olditem_level.push(v); Ok(()) } else { Err(()) }},
-            e => { error!("Invalid data in post request: {}", e); Err(()) },
-        } {
+    let id: i32 = session.get("id").unwrap().unwrap();
+
+    let mut form = match EditForm::parse(form.into_inner()) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Invalid data in post request: {}", e);
             return HttpResponse::BadRequest().body("Could not parse data");
-        }
-    }
+        },
+    };
 
-    let mut trans = match pool.get_ref().begin().await {
+    let mut trans = match repo.begin().await {
         Ok(t) => t,
         Err(_) => panic!("Failed to connect to db"),
     };
 
-    for (cuid, cid, cname, oldcname, cclass_id, oldcclass_id, item_level, olditem_level) in
-            izip!(&update.cuid, &update.cid, &update.cname, &update.oldcname, &update.cclass_id, &update.oldcclass_id, &update.item_level, &update.olditem_level) {
-        
-        if cname != oldcname || cclass_id != oldcclass_id || item_level != olditem_level {
-            match sqlx::query!("
-                UPDATE characters
-                SET name = ?, class_id = ?, item_level = ?
-                WHERE id = ? AND user_id = ? AND ? = ?",
-                cname, cclass_id, item_level, cid, cuid, cuid, &session.get::<i32>("id").unwrap())
-            .execute(&mut trans).await {
-                Ok(_) => (),
-                Err(e) => error!("{:?}", e),
+    let data = game_data.get();
+    let classes = &data.classes;
+
+    let current: HashMap<i32, Character> = match repo.characters_for_update(&mut trans, id).await {
+        Ok(v) => v.into_iter().map(|c| (c.id, c)).collect(),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    if form.rows.iter().any(|r| !current.contains_key(&r.id)) {
+        return HttpResponse::Forbidden().body("You can only edit your own characters");
+    }
+
+    let class_ids: Vec<i32> = classes.iter().map(|c| c.id).collect();
+
+    let Some((rows, roster_level)) = form.validate(&class_ids) else {
+        return render_edit_chars(&tera, classes, &form, HttpResponse::BadRequest());
+    };
+
+    let mut conflicts = Vec::new();
+
+    for (pos, row) in rows.iter().enumerate() {
+        let cur = &current[&row.id];
+
+        if row.version != cur.version {
+            conflicts.push(row.id);
+            continue;
+        }
+
+        if row.differs(cur) {
+            match repo.update_character(&mut trans, &row.to_character(id), Some(row.version)).await {
+                Ok(true) => (),
+                Ok(false) => {
+                    conflicts.push(row.id);
+                    continue;
+                },
+                Err(e) => {
+                    error!("{:?}", e);
+                    return HttpResponse::InternalServerError().body("Failed to update characters. Please try again later");
+                },
+            };
+
+            if row.item_level != cur.item_level {
+                if let Err(e) = repo.record_item_level(&mut trans, row.id, row.item_level).await {
+                    error!("{:?}", e);
+                    return HttpResponse::InternalServerError().body("Failed to update characters. Please try again later");
+                }
+            }
+        }
+
+        // Rows are submitted in the order the user arranged them in
+        if pos as i32 != cur.sort_order {
+            if let Err(e) = repo.set_sort_order(&mut trans, id, row.id, pos as i32).await {
+                error!("{:?}", e);
+                return HttpResponse::InternalServerError().body("Failed to update characters. Please try again later");
             }
         }
     }
 
+    if let Some(roster_level) = roster_level {
+        if let Err(e) = repo.set_roster_level(&mut trans, id, roster_level).await {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Failed to update characters. Please try again later");
+        }
+    }
+
+    if conflicts.is_empty() {
+        if let Err(e) = trans.commit().await {
+            error!("{}", e);
+            return HttpResponse::InternalServerError().body("Failed to update characters. Please try again later");
+        }
+
+        live.publish_for_user(repo.as_ref(), Change::Character { user_id: id }).await;
+
+        return HttpResponse::SeeOther().insert_header((LOCATION, "chars")).finish();
+    }
+
+    // Show the current state of characters that were changed elsewhere and keep the users input for the others
+    let fresh = match repo.characters(&mut trans, id).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
     if let Err(e) = trans.commit().await {
         error!("{}", e);
         return HttpResponse::InternalServerError().body("Failed to update characters. Please try again later");
     }
 
-    return HttpResponse::SeeOther().insert_header((LOCATION, "chars")).finish();
+    live.publish_for_user(repo.as_ref(), Change::Character { user_id: id }).await;
+
+    for c in fresh.iter() {
+        if let Some(row) = form.row_mut(c.id) {
+            if conflicts.contains(&c.id) {
+                *row = CharRow::from(c);
+                row.errors.push("This character was changed elsewhere, your changes were discarded".to_string());
+            } else {
+                row.version = c.version;
+            }
+        }
+    }
+
+    form.errors.push("Some characters were changed elsewhere. All other changes have been saved.".to_string());
+
+    render_edit_chars(&tera, classes, &form, HttpResponse::Conflict())
+}
+
+fn render_edit_chars(
+    tera: &Tera,
+    classes: &[Class],
+    form: &EditForm,
+    mut res: HttpResponseBuilder,
+) -> HttpResponse {
+    let mut con = Context::new();
+    con.insert("classes", classes);
+    con.insert("chars", &form.rows);
+    con.insert("roster_level", &form.roster_level);
+    con.insert("errors", &form.errors);
+
+    res.body(tera.render("edit_characters.html", &con).unwrap())
 }
 
 #[get("/me/edit_chars")]
 async fn edit_chars(
     tera: web::Data<Tera>,
     session: Session,
-    pool: web::Data<MySqlPool>,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
 ) -> impl Responder {
-    let mut trans = match pool.get_ref().begin().await {
+    let id: i32 = session.get("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
         Ok(t) => t,
         Err(_) => panic!("Failed to connect to db"),
     };
 
-    let classes = sqlx::query_as!(
-        Class,
-        "SELECT * FROM classes"
-    ).fetch_all(&mut trans)
-    .await
-    .unwrap();
-
-    let mut con = Context::new();
-    con.insert("classes", &classes);
-
-    let chars = sqlx::query_as!(
-        Character,
-        "SELECT * FROM characters WHERE user_id = ? ORDER BY item_level DESC",
-        &session.get::<i32>("id").unwrap())
-        .fetch_all(&mut trans)
+    let chars = repo.characters(&mut trans, id)
         .await
         .unwrap();
 
-    con.insert("chars", &chars);
+    let roster_level = repo.roster_level(&mut trans, id)
+        .await
+        .unwrap();
 
     //Ignore errors
     trans.commit().await.ok();
 
-    return HttpResponse::Ok().body(
-        tera.render("edit_characters.html", &con).unwrap()
-    );
+    let form = EditForm {
+        rows: chars.iter().map(CharRow::from).collect(),
+        roster_level: roster_level.to_string(),
+        errors: Vec::new(),
+    };
+
+    render_edit_chars(&tera, &game_data.get().classes, &form, HttpResponse::Ok())
 }
 
 #[get("/me/add_char")]
 async fn add_char(
     tera: web::Data<Tera>,
     session: Session,
-    pool: web::Data<MySqlPool>,
+    game_data: web::Data<GameData>,
 ) -> impl Responder {
-    let trans = pool.get_ref().begin().await;
-
-    let mut trans = match trans {
-        Ok(t) => t,
-        Err(_) => panic!("Failed to connect to db"),
-    };
-
-    let classes = sqlx::query_as!(
-        Class,
-        "SELECT * FROM classes
-        ORDER BY name"
-    ).fetch_all(&mut trans)
-    .await
-    .unwrap();
-
     let mut con = Context::new();
     con.insert("uid", &session.get::<i32>("id").unwrap());
-    con.insert("classes", &classes);
-
-    //Ignore errors
-    trans.commit().await.ok();
+    con.insert("classes", &game_data.get().classes);
 
-    return HttpResponse::Ok().body(
+    HttpResponse::Ok().body(
         tera.render("add_character.html", &con).unwrap()
-    );
+    )
 }
 
 #[post("/me/add_char")]
 async fn post_add_char(
     session: Session,
-    pool: web::Data<MySqlPool>,
+    repo: web::Data<dyn Repository>,
+    live: web::Data<Broadcaster>,
     chara: web::Form<Character>,
 ) -> impl Responder {
     let id: i32 = session.get("id").unwrap().unwrap();
 
-    let trans = pool.get_ref().begin().await;
-
-    let mut trans = match trans {
-        Ok(t) => t,
-        Err(_) => panic!("Failed to connect to db"),
-    };
-
     if id != chara.user_id {
         return HttpResponse::BadRequest().body("[softly]<br>Don't");
     }
 
-    let res = match query_as!(
-        Character,
-        "INSERT INTO characters (user_id, name, class_id, item_level) VALUES (?, ?, ?, ?)",
-        chara.user_id,
-        chara.name,
-        chara.class_id,
-        chara.item_level
-    ).execute(&mut trans)
-    .await {
-        Ok(_) => HttpResponse::SeeOther().insert_header((LOCATION, "chars")).finish(),
-        Err(_) => HttpResponse::InternalServerError().body("Could not create Character"),
-    };
+    if let Err(e) = validate_name(&chara.name).and_then(|_| validate_item_level(chara.item_level)) {
+        return HttpResponse::BadRequest().body(e);
+    }
 
-    if let Err(e) = trans.commit().await {
-        error!("Database Error: {:?}", e);
-        return HttpResponse::InternalServerError().body("Could not write char to database");
+    if let Err(e) = repo.add_character(id, &chara.name, chara.class_id, chara.item_level).await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Could not create Character");
     }
 
-    return res;
+    live.publish_for_user(repo.as_ref(), Change::Character { user_id: id }).await;
+
+    HttpResponse::SeeOther().insert_header((LOCATION, "chars")).finish()
 }
 
 #[get("/me/chars")]
 async fn show_chars(
     session: Session,
-    pool: web::Data<MySqlPool>,
+    repo: web::Data<dyn Repository>,
     tera: web::Data<Tera>,
+    game_data: web::Data<GameData>,
 ) -> impl Responder {
     let id: i32 = session.get("id").unwrap().unwrap();
 
-    let trans = pool.get_ref().begin().await;
+    let trans = repo.begin().await;
 
     let mut trans = match trans {
         Ok(t) => t,
         Err(_) => panic!("Failed to connect to db"),
     };
 
-    //TODO maybe non-repeatable read for performance
-    let chars = sqlx::query_as!(
-        RenderableChar,
-        "SELECT ch.id, ch.name as name, cl.name as class, ch.item_level 
-        FROM characters ch 
-        JOIN classes cl ON ch.class_id = cl.id
-        WHERE ch.user_id = ?
-        ORDER BY ch.item_level DESC",
-        id
-    )
-    .fetch_all(&mut trans).await;
-
-    let name = sqlx::query!(
-        "SELECT username FROM users WHERE id = ?",
-        id
-    ).fetch_one(&mut trans).await;
-
-    let activities: Vec<Activity> = sqlx::query!(
-        "SELECT id, name, difficulty FROM raids ORDER BY id"
-        ).fetch_all(&mut trans)
-        .await.unwrap().iter().map(|e| {
-            Activity{id: e.id, name: e.name.clone(), difficulty: e.difficulty.clone(), completed: false, available: true}
+    let charc = match load_characters_page(repo.as_ref(), &mut trans, id, &game_data.get().rules).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    if let Err(e) = trans.commit().await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Database error");
+    }
+
+    let html_str = tera.render("characters.html", &Context::from_serialize(&charc).unwrap()).unwrap();
+
+    HttpResponse::Ok()
+        .body(html_str)
+}
+
+/// Loads the roster page of a user. The number of queries does not depend on the size of the roster.
+pub(crate) async fn load_characters_page(
+    repo: &dyn Repository,
+    conn: &mut AnyConnection,
+    id: i32,
+    rules: &RaidRules,
+) -> Result<CharContext, RepoError> {
+    let chars = repo.character_overviews(&mut *conn, id).await?;
+    let name = repo.username(&mut *conn, id).await?;
+
+    let activities: Vec<Activity> = rules.raids.iter().map(|e| {
+            Activity{id: e.id, name: e.name.clone(), difficulty: e.difficulty.clone(), completed: false, took_gold: false, available: true}
         }).collect();
 
-    let charc = CharContext {
-        activities: activities.clone(),
-        name: name.unwrap().username,
-        chars: match chars {
-            Ok(c) => {
-                let mut res: Vec<CompleteChar> = Vec::new();
-                for e in c {
-                    let activities = sqlx::query!(
-                        "SELECT 
-                            r.id, r.name, r.difficulty,
-                            IF(ur.character_id IS NOT NULL, 1, 0) AS completed,
-                            IF(
-                                ((r.three_weekly = 1 AND
-                                (
-                                    SELECT COUNT(*) 
-                                    FROM user_raids AS ur2
-                                    INNER JOIN raids AS r2 ON ur2.raid_id = r2.id
-                                    WHERE ur2.character_id = ? AND r2.three_weekly = 1
-                                ) >= 3) OR
-                                EXISTS (
-                                    SELECT 1 
-                                    FROM user_raids AS ur3
-                                    INNER JOIN raids AS r3 ON ur3.raid_id = r3.id
-                                    WHERE ur3.character_id = ? AND r3.name = r.name
-                                ))
-                                OR (
-                                    EXISTS(
-                                        SELECT raid FROM raid_prerequisites WHERE raid NOT IN (
-                                            SELECT raid FROM user_raids ur5 JOIN raid_prerequisites rp ON ur5.raid_id = rp.requires WHERE ur5.character_id = ?
-                                        ) AND raid = r.id
-                                    )
-                                ) 
-                                OR (
-                                    EXISTS(
-                                        SELECT *
-                                        FROM characters
-                                        WHERE id = ? AND item_level < r.required_item_level
-                                    )
-                                ), 0, 1
-                            ) AS available
-                        FROM
-                            raids AS r
-                        LEFT JOIN 
-                            user_raids AS ur ON r.id = ur.raid_id AND ur.character_id = ? ORDER BY r.id;",
-                        e.id,
-                        e.id,
-                        e.id,
-                        e.id,
-                        e.id,
-                    ).fetch_all(&mut trans).await.unwrap();
-
-                    res.push(CompleteChar{ 
-                        id: e.id, 
-                        name: e.name.clone(), 
-                        class: e.class.clone(), 
-                        item_level: e.item_level, 
-                        activities: activities.iter().map(|f| {
-                            Activity{ id: f.id, name: f.name.clone(), difficulty: f.difficulty.clone(), completed: f.completed == 1, available: f.available == Some(1) }
-                        }).collect()
-                    });
-                }
-                res
-            },
-            Err(_) => return HttpResponse::InternalServerError().body("Database error"),
-    }};
+    let tasks = repo.tasks(&mut *conn).await?;
+
+    let mut task_states: HashMap<i32, Vec<TaskState>> = HashMap::new();
+
+    for e in repo.task_states_of_user(&mut *conn, id).await? {
+        task_states.entry(e.character_id).or_default().push(TaskState {
+            id: e.task_id,
+            completed: e.completed,
+            rest_bonus: e.rest_bonus,
+        });
+    }
+
+    let clears = repo.clears_of_user(conn, id).await?;
+    let clears = rules::clears_by_character(&clears);
+
+    let chars: Vec<CompleteChar> = chars.into_iter().map(|e| {
+        let states = rules.states(e.item_level, e.gold_earner, clears.get(&e.id).map(Vec::as_slice).unwrap_or_default());
+
+        CompleteChar {
+            activities: rules.raids.iter().zip(states).map(|(r, s)| Activity {
+                id: r.id,
+                name: r.name.clone(),
+                difficulty: r.difficulty.clone(),
+                completed: s.completed,
+                took_gold: s.took_gold,
+                available: s.available(),
+            }).collect(),
+            tasks: task_states.remove(&e.id).unwrap_or_default(),
+            id: e.id,
+            name: e.name,
+            class: e.class,
+            item_level: e.item_level,
+            gold_earner: e.gold_earner,
+        }
+    }).collect();
+
+    Ok(CharContext {
+        dashboard: dashboard::summarize(&rules.raids, &chars),
+        activities,
+        tasks,
+        name,
+        chars,
+    })
+}
+
+#[derive(Serialize)]
+struct Milestone {
+    raid: String,
+    required_item_level: i32,
+    reached: Option<String>,
+}
+
+#[get("/me/chars/{id}")]
+async fn show_char(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    tera: web::Data<Tera>,
+    game_data: web::Data<GameData>,
+    cid: web::Path<(i32,)>,
+) -> impl Responder {
+    let id: i32 = session.get("id").unwrap().unwrap();
+    let cid = cid.0;
 
-    if let Err(_) = trans.commit().await {
-        error!("Database error");
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to db"),
+    };
+
+    let chara = match repo.character_overview(&mut trans, id, cid).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return HttpResponse::Forbidden().body("This is not your character"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let history = match repo.item_level_history(&mut trans, cid).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    if let Err(e) = trans.commit().await {
+        error!("{:?}", e);
         return HttpResponse::InternalServerError().body("Database error");
     }
 
-    let html_str = tera.render("characters.html", &Context::from_serialize(&charc).unwrap()).unwrap();
+    let mut thresholds: Vec<(String, i32)> = game_data.get().rules.raids.iter()
+        .map(|r| (format!("{} {}", r.name, r.difficulty), r.required_item_level))
+        .collect();
+    thresholds.sort_by_key(|(_, required)| *required);
+
+    let milestones: Vec<Milestone> = thresholds.iter().map(|(raid, required)| Milestone {
+        raid: raid.clone(),
+        required_item_level: *required,
+        reached: history.iter()
+            .find(|(_, ilvl)| ilvl >= required)
+            .map(|(t, _)| t.format("%Y-%m-%d").to_string()),
+    }).collect();
+
+    let mut con = Context::new();
+    con.insert("name", &chara.name);
+    con.insert("class", &chara.class);
+    con.insert("item_level", &chara.item_level);
+    con.insert("chart", &chart::ilvl_chart(&history, &thresholds));
+    con.insert("milestones", &milestones);
+
+    HttpResponse::Ok().body(tera.render("character.html", &con).unwrap())
+}
+
+#[get("/me/chars/{id}/delete")]
+async fn delete_char(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    tera: web::Data<Tera>,
+    cid: web::Path<(i32,)>,
+) -> impl Responder {
+    let id: i32 = session.get("id").unwrap().unwrap();
+
+    let mut conn = match repo.acquire().await {
+        Ok(c) => c,
+        Err(_) => panic!("Failed to connect to db"),
+    };
+
+    let chara = match repo.character_overview(&mut conn, id, cid.0).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return HttpResponse::Forbidden().body("This is not your character"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
 
-    return HttpResponse::Ok()
-        .body(html_str);
+    let mut con = Context::new();
+    con.insert("id", &chara.id);
+    con.insert("name", &chara.name);
+
+    HttpResponse::Ok().body(tera.render("delete_character.html", &con).unwrap())
+}
+
+#[post("/me/chars/{id}/delete")]
+async fn delete_char_post(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    live: web::Data<Broadcaster>,
+    cid: web::Path<(i32,)>,
+) -> impl Responder {
+    let id: i32 = session.get("id").unwrap().unwrap();
+    let cid = cid.0;
+
+    // Clears and tasks of the character are removed with it
+    match repo.delete_character(id, cid).await {
+        Ok(false) => return HttpResponse::Forbidden().body("This is not your character"),
+        Ok(true) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    live.publish_for_user(repo.as_ref(), Change::Character { user_id: id }).await;
+
+    HttpResponse::SeeOther().insert_header((LOCATION, "/auth/me/chars")).finish()
 }
 
 #[post("/me/update_activity")]
 async fn update_activity(
     session: Session,
-    pool: web::Data<MySqlPool>,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    live: web::Data<Broadcaster>,
+    metrics: web::Data<Metrics>,
     update: web::Form<ActivityUpdate>,
 ) -> impl Responder {
     let id: i32 = session.get("id").unwrap().unwrap();
 
-    let trans = pool.get_ref().begin().await;
+    let trans = repo.begin().await;
 
     let mut trans = match trans {
         Ok(t) => t,
         Err(_) => panic!("Failed to connect to db"),
     };
 
-    let Ok(amount) = sqlx::query!("SELECT COUNT(*) AS count FROM characters WHERE user_id = ? AND id = ?",
-        id,
-        update.character_id)
-        .fetch_one(&mut trans).await else {
-            return HttpResponse::Forbidden().finish();
-        };
-
-    if amount.count == 0 {
+    let Ok(Some(chara)) = repo.character_overview(&mut trans, id, update.character_id).await else {
         return HttpResponse::Forbidden().finish();
-    }
-
+    };
 
     match update.completed {
         true => {
-            if let Err(e) = sqlx::query!("INSERT INTO user_raids VALUES (?, ?, ?)",
-                id,
-                update.character_id,
-                update.activity_id,
-            ).execute(&mut trans)
-            .await {
+            let clear = Clear {
+                character_id: update.character_id,
+                raid_id: update.activity_id,
+                took_gold: chara.gold_earner && update.took_gold.unwrap_or(true),
+            };
+
+            if let Err(e) = repo.add_clear(&mut trans, id, &clear).await {
                 error!("{:?}", e);
                 return HttpResponse::BadRequest().finish();
             }
         },
         false => {
-            if let Err(_) = sqlx::query!("DELETE FROM user_raids WHERE user_id = ? AND character_id = ? AND raid_id = ?",
-                id,
-                update.character_id,
-                update.activity_id,
-            ).execute(&mut trans).await {
+            if let Err(e) = repo.remove_clear(&mut trans, id, update.character_id, update.activity_id).await {
+                error!("{:?}", e);
                 return HttpResponse::InternalServerError().finish();
             }
         },
     }
 
-    let Ok(res) = sqlx::query_as!(
-        RaidAvailable,
-        "SELECT 
-            r.id,
-            IF(ur.character_id IS NOT NULL, 1, 0) AS completed,
-            IF(
-                ((r.three_weekly = 1 AND
-                (
-                    SELECT COUNT(*) 
-                    FROM user_raids AS ur2
-                    INNER JOIN raids AS r2 ON ur2.raid_id = r2.id
-                    WHERE ur2.character_id = ? AND r2.three_weekly = 1
-                ) >= 3) OR
-                EXISTS (
-                    SELECT 1 
-                    FROM user_raids AS ur3
-                    INNER JOIN raids AS r3 ON ur3.raid_id = r3.id
-                    WHERE ur3.character_id = ? AND r3.name = r.name
-                ))
-                OR (
-                    EXISTS(
-                        SELECT raid FROM raid_prerequisites WHERE raid NOT IN (
-                            SELECT raid FROM user_raids ur5 JOIN raid_prerequisites rp ON ur5.raid_id = rp.requires WHERE ur5.character_id = ?
-                        ) AND raid = r.id
-                    )
-                ) OR (
-                    EXISTS(
-                        SELECT *
-                        FROM characters
-                        WHERE id = ? AND item_level < r.required_item_level
-                    )
-                ), 0, 1
-            ) AS available
-        FROM
-            raids AS r
-        LEFT JOIN 
-            user_raids AS ur ON r.id = ur.raid_id AND ur.character_id = ? ORDER BY r.id;",
-        update.character_id,
-        update.character_id,
-        update.character_id,
-        update.character_id,
-        update.character_id,
-    )
-        .fetch_all(&mut trans)
-        .await
-    else {
-        panic!("asdf");
+    let data = game_data.get();
+
+    let clears = match repo.clears_of_character(&mut trans, update.character_id).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        },
     };
 
-    if let Err(_) = trans.commit().await {
+    let res: Vec<RaidAvailable> = data.rules.states(chara.item_level, chara.gold_earner, &clears.iter().collect::<Vec<_>>())
+        .into_iter()
+        .map(|s| RaidAvailable {
+            id: s.raid_id,
+            completed: s.completed,
+            took_gold: s.took_gold,
+            available: s.available(),
+        })
+        .collect();
+
+    if let Err(e) = trans.commit().await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Failed to update db");
+    }
+
+    metrics.activity_toggles.inc();
+    live.publish_for_user(repo.as_ref(), Change::Activity { user_id: id }).await;
+
+    HttpResponse::Ok().json(res)
+}
+
+#[post("/me/update_task")]
+async fn update_task(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    update: web::Form<TaskUpdate>,
+) -> impl Responder {
+    let id: i32 = session.get("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to db"),
+    };
+
+    let Ok(Some(_)) = repo.character_overview(&mut trans, id, update.character_id).await else {
+        return HttpResponse::Forbidden().finish();
+    };
+
+    if let Err(e) = repo.set_task(&mut trans, update.character_id, update.task_id, update.completed).await {
+        error!("{:?}", e);
+        return HttpResponse::BadRequest().finish();
+    }
+
+    let res: Vec<TaskState> = match repo.task_states_of_character(&mut trans, update.character_id).await {
+        Ok(v) => v.iter().map(|e| TaskState { id: e.task_id, completed: e.completed, rest_bonus: e.rest_bonus }).collect(),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().finish();
+        },
+    };
+
+    if let Err(e) = trans.commit().await {
+        error!("{:?}", e);
         return HttpResponse::InternalServerError().body("Failed to update db");
     }
 
-    return HttpResponse::Ok().json(res);
+    HttpResponse::Ok().json(res)
 }
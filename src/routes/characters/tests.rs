@@ -0,0 +1,409 @@
+//! Handler tests for the roster pages, run against the in-process app of [`crate::testing`].
+
+use actix_web::http::StatusCode;
+use serde_json::Value;
+
+use crate::data::GOLD_EARNERS;
+use crate::testing::{TestApp, TestResponse, TestUser};
+
+/// A raid without prerequisites, so every character above its item level can clear it
+async fn open_raid(app: &TestApp) -> i32 {
+    sqlx::query_scalar(
+        "SELECT id FROM raids
+        WHERE id NOT IN (SELECT raid FROM raid_prerequisites)
+        ORDER BY required_item_level, id
+        LIMIT 1"
+    ).fetch_one(app.pool())
+    .await
+    .unwrap()
+}
+
+async fn character_exists(app: &TestApp, cid: i32) -> bool {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM characters WHERE id = ?")
+        .bind(cid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap() > 0
+}
+
+async fn clears(app: &TestApp, cid: i32) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM user_raids WHERE character_id = ?")
+        .bind(cid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap()
+}
+
+async fn toggle_raid(app: &TestApp, user: &TestUser, cid: i32, rid: i32, completed: bool) -> TestResponse {
+    let form = [
+        ("character_id", cid.to_string()),
+        ("activity_id", rid.to_string()),
+        ("completed", completed.to_string()),
+    ];
+    app.post_as(user, "/auth/me/update_activity", &form).await
+}
+
+/// The bulk editor form with one unchanged row for the character, as the page submits it
+async fn edit_form(app: &TestApp, cid: i32) -> Vec<(String, String)> {
+    let (version, name, class_id, item_level): (i32, String, i32, i32) = sqlx::query_as(
+        "SELECT version, name, class_id, item_level FROM characters WHERE id = ?"
+    ).bind(cid)
+    .fetch_one(app.pool())
+    .await
+    .unwrap();
+
+    let mut form = vec![("cid[]".to_string(), cid.to_string())];
+    for (field, value) in [
+        ("version", version.to_string()),
+        ("name", name),
+        ("class_id", class_id.to_string()),
+        ("item_level", item_level.to_string()),
+        ("gold_earner", "true".to_string()),
+        ("build", String::new()),
+        ("stats", String::new()),
+        ("card_set", String::new()),
+        ("combat_power", String::new()),
+        ("note", String::new()),
+        ("main", "false".to_string()),
+    ] {
+        form.push((format!("{}[{}]", field, cid), value));
+    }
+    form.push(("roster_level".to_string(), String::new()));
+
+    form
+}
+
+fn set(form: &mut [(String, String)], key: &str, value: &str) {
+    let field = form.iter_mut().find(|(k, _)| k == key).expect("field is part of the form");
+    field.1 = value.to_string();
+}
+
+#[actix_web::test]
+async fn add_char_form_renders() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+
+    let res = app.get_as(&alice, "/auth/me/add_char").await;
+
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains(&format!("value=\"{}\"", alice.id)));
+}
+
+#[actix_web::test]
+async fn post_add_char_creates_character() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+
+    let cid = app.add_character(&alice, "Alpha", 1580).await;
+
+    let history: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM character_ilvl_history WHERE character_id = ? AND item_level = 1580")
+        .bind(cid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(history, 1, "the initial item level is recorded");
+}
+
+#[actix_web::test]
+async fn post_add_char_limits_gold_earners() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+
+    for i in 0..=GOLD_EARNERS {
+        app.add_character(&alice, &format!("Alt{}", i), 1500).await;
+    }
+
+    let earners: Vec<bool> = sqlx::query_scalar("SELECT gold_earner FROM characters WHERE user_id = ? ORDER BY sort_order")
+        .bind(alice.id)
+        .fetch_all(app.pool())
+        .await
+        .unwrap();
+
+    assert_eq!(earners.len(), GOLD_EARNERS + 1);
+    assert!(earners[..GOLD_EARNERS].iter().all(|e| *e));
+    assert!(!earners[GOLD_EARNERS], "the roster already has {} gold earners", GOLD_EARNERS);
+}
+
+#[actix_web::test]
+async fn post_add_char_rejects_other_user() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+
+    let form = [
+        ("id", "0".to_string()),
+        ("user_id", bob.id.to_string()),
+        ("name", "Sneaky".to_string()),
+        ("class_id", "1".to_string()),
+        ("item_level", "1500".to_string()),
+    ];
+    let res = app.post_as(&alice, "/auth/me/add_char", &form).await;
+
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM characters")
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[actix_web::test]
+async fn post_add_char_validates_name_and_item_level() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+
+    for (name, item_level) in [("A", "1500"), ("A name that is far too long", "1500"), ("Alice", "-5"), ("Alice", "99999")] {
+        let form = [
+            ("id", "0".to_string()),
+            ("user_id", alice.id.to_string()),
+            ("name", name.to_string()),
+            ("class_id", "1".to_string()),
+            ("item_level", item_level.to_string()),
+        ];
+        let res = app.post_as(&alice, "/auth/me/add_char", &form).await;
+
+        assert_eq!(res.status, StatusCode::BAD_REQUEST, "{} {}", name, item_level);
+    }
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM characters")
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[actix_web::test]
+async fn show_chars_lists_roster() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    app.add_character(&alice, "Alpha", 1580).await;
+    app.add_character(&alice, "Beta", 1540).await;
+
+    let res = app.get_as(&alice, "/auth/me/chars").await;
+
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("alice's Characters"));
+    assert!(res.body.contains("Alpha"));
+    assert!(res.body.contains("Beta"));
+}
+
+#[actix_web::test]
+async fn show_char_is_private() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let cid = app.add_character(&alice, "Alpha", 1580).await;
+
+    let res = app.get_as(&alice, &format!("/auth/me/chars/{}", cid)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("Alpha"));
+
+    let res = app.get_as(&bob, &format!("/auth/me/chars/{}", cid)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn delete_char_asks_for_confirmation() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let cid = app.add_character(&alice, "Alpha", 1580).await;
+
+    let res = app.get_as(&alice, &format!("/auth/me/chars/{}/delete", cid)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("Alpha"));
+
+    let res = app.get_as(&bob, &format!("/auth/me/chars/{}/delete", cid)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn delete_char_post_removes_character_and_clears() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let cid = app.add_character(&alice, "Alpha", 1620).await;
+    let rid = open_raid(&app).await;
+    toggle_raid(&app, &alice, cid, rid, true).await;
+
+    let res = app.post_as(&bob, &format!("/auth/me/chars/{}/delete", cid), &()).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert!(character_exists(&app, cid).await);
+
+    let res = app.post_as(&alice, &format!("/auth/me/chars/{}/delete", cid), &()).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert_eq!(res.location(), Some("/auth/me/chars"));
+    assert!(!character_exists(&app, cid).await);
+    assert_eq!(clears(&app, cid).await, 0);
+}
+
+#[actix_web::test]
+async fn update_activity_toggles_clear() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let cid = app.add_character(&alice, "Alpha", 1620).await;
+    let rid = open_raid(&app).await;
+
+    let res = toggle_raid(&app, &alice, cid, rid, true).await;
+    assert_eq!(res.status, StatusCode::OK);
+
+    let states: Value = serde_json::from_str(&res.body).unwrap();
+    let state = states.as_array().unwrap().iter().find(|s| s["id"] == rid).expect("state of the raid");
+    assert_eq!(state["completed"], true);
+    assert_eq!(state["took_gold"], true);
+    assert_eq!(clears(&app, cid).await, 1);
+
+    let res = toggle_raid(&app, &alice, cid, rid, false).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(clears(&app, cid).await, 0);
+}
+
+#[actix_web::test]
+async fn update_activity_rejects_other_user() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let cid = app.add_character(&alice, "Alpha", 1620).await;
+    let rid = open_raid(&app).await;
+
+    let res = toggle_raid(&app, &bob, cid, rid, true).await;
+
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert_eq!(clears(&app, cid).await, 0);
+}
+
+#[actix_web::test]
+async fn update_task_marks_task() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let cid = app.add_character(&alice, "Alpha", 1620).await;
+    let tid: i32 = sqlx::query_scalar("SELECT id FROM tasks ORDER BY id LIMIT 1")
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+
+    let form = [
+        ("character_id", cid.to_string()),
+        ("task_id", tid.to_string()),
+        ("completed", "true".to_string()),
+    ];
+
+    let res = app.post_as(&bob, "/auth/me/update_task", &form).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    let res = app.post_as(&alice, "/auth/me/update_task", &form).await;
+    assert_eq!(res.status, StatusCode::OK);
+
+    let states: Value = serde_json::from_str(&res.body).unwrap();
+    let state = states.as_array().unwrap().iter().find(|s| s["id"] == tid).expect("state of the task");
+    assert_eq!(state["completed"], true);
+}
+
+#[actix_web::test]
+async fn edit_chars_shows_roster() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    app.add_character(&alice, "Alpha", 1580).await;
+
+    let res = app.get_as(&alice, "/auth/me/edit_chars").await;
+
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("Alpha"));
+}
+
+#[actix_web::test]
+async fn edit_chars_post_saves_changes() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let cid = app.add_character(&alice, "Alpha", 1580).await;
+
+    let mut form = edit_form(&app, cid).await;
+    set(&mut form, &format!("name[{}]", cid), "Gamma");
+    set(&mut form, &format!("item_level[{}]", cid), "1600");
+    set(&mut form, "roster_level", "250");
+
+    let res = app.post_as(&alice, "/auth/me/edit_chars", &form).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER, "{}", res.body);
+
+    let (name, item_level, version): (String, i32, i32) = sqlx::query_as("SELECT name, item_level, version FROM characters WHERE id = ?")
+        .bind(cid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!((name.as_str(), item_level, version), ("Gamma", 1600, 1));
+
+    let history: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM character_ilvl_history WHERE character_id = ?")
+        .bind(cid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(history, 2, "the new item level is recorded");
+
+    let roster_level: i32 = sqlx::query_scalar("SELECT roster_level FROM users WHERE id = ?")
+        .bind(alice.id)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(roster_level, 250);
+}
+
+#[actix_web::test]
+async fn edit_chars_post_reports_invalid_rows() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let cid = app.add_character(&alice, "Alpha", 1580).await;
+
+    let mut form = edit_form(&app, cid).await;
+    set(&mut form, &format!("name[{}]", cid), "A");
+
+    let res = app.post_as(&alice, "/auth/me/edit_chars", &form).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert!(res.body.contains("Name must be between"));
+
+    let res = app.post_as(&alice, "/auth/me/edit_chars", &[("name[1]", "Alpha")]).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert_eq!(res.body, "Could not parse data");
+}
+
+#[actix_web::test]
+async fn edit_chars_post_detects_concurrent_edit() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let cid = app.add_character(&alice, "Alpha", 1580).await;
+
+    let stale = edit_form(&app, cid).await;
+
+    let mut form = stale.clone();
+    set(&mut form, &format!("name[{}]", cid), "Gamma");
+    let res = app.post_as(&alice, "/auth/me/edit_chars", &form).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+
+    let mut form = stale;
+    set(&mut form, &format!("name[{}]", cid), "Delta");
+    let res = app.post_as(&alice, "/auth/me/edit_chars", &form).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert!(res.body.contains("changed elsewhere"));
+
+    let name: String = sqlx::query_scalar("SELECT name FROM characters WHERE id = ?")
+        .bind(cid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(name, "Gamma");
+}
+
+#[actix_web::test]
+async fn edit_chars_post_rejects_other_users_characters() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let cid = app.add_character(&alice, "Alpha", 1580).await;
+
+    let mut form = edit_form(&app, cid).await;
+    set(&mut form, &format!("name[{}]", cid), "Stolen");
+
+    let res = app.post_as(&bob, "/auth/me/edit_chars", &form).await;
+
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+}
@@ -2,7 +2,6 @@ use actix_web::HttpResponse;
 use actix_web::Responder;
 use actix_web::get;
 use actix_web::http::header::ContentType;
-use mime;
 
 #[get("styles.css")]
 pub async fn base_styles() -> impl Responder{
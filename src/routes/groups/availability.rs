@@ -0,0 +1,520 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+
+use actix_session::Session;
+use actix_web::{get, post, web, HttpResponse, HttpResponseBuilder, Responder, http::header};
+use chrono::{Datelike, DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::AnyConnection;
+use tera::{Tera, Context};
+
+use crate::data::{AvailabilityException, AvailabilityWindow};
+use crate::repo::{RepoError, Repository};
+
+const HEATMAP_DAYS: i64 = 7;
+const SLOT_MINUTES: i64 = 60;
+const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+#[derive(Serialize, Debug)]
+pub(super) struct Heatmap {
+    timezone: String,
+    days: Vec<String>,
+    rows: Vec<HeatmapRow>,
+}
+
+impl Heatmap {
+    /// The timezone the heatmap is shown in
+    pub fn tz(&self) -> Tz {
+        parse_tz(&self.timezone).unwrap_or(Tz::UTC)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct HeatmapRow {
+    time: String,
+    cells: Vec<HeatmapCell>,
+}
+
+#[derive(Serialize, Debug)]
+struct HeatmapCell {
+    names: Vec<String>,
+    /// Share of the group that is available, between 0 and 1
+    intensity: f64,
+}
+
+/// The availability of a single member as utc intervals
+#[derive(Default)]
+struct MemberTimes {
+    name: String,
+    /// Sorted by start
+    available: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    absent: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl MemberTimes {
+    /// Whether the member is available for the whole interval. Nobody is available in an empty
+    /// one, like the hour skipped by a daylight saving switch.
+    fn covers(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        if from >= to || self.absent.iter().any(|(s, e)| *s < to && *e > from) {
+            return false;
+        }
+
+        let mut covered = from;
+        for (s, e) in self.available.iter() {
+            if *s > covered {
+                break;
+            }
+            if *e > covered {
+                covered = *e;
+            }
+            if covered >= to {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Converts a local time to utc. Times skipped by a daylight saving switch are moved past the gap.
+pub(super) fn to_utc(tz: &Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    tz.from_local_datetime(&local).earliest()
+        .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+/// The utc interval of a window on a given day. An end before or equal to the start wraps into the next day.
+fn interval(tz: &Tz, day: NaiveDate, start: NaiveTime, end: NaiveTime) -> (DateTime<Utc>, DateTime<Utc>) {
+    let end_day = if end > start { day } else { day + Duration::days(1) };
+    (to_utc(tz, day.and_time(start)), to_utc(tz, end_day.and_time(end)))
+}
+
+pub(super) fn parse_tz(tz: &str) -> Option<Tz> {
+    tz.parse::<Tz>().ok()
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+        .map_err(|_| format!("Invalid time {}", time))
+}
+
+/// Builds the heatmap of the `HEATMAP_DAYS` days starting at `start`, as seen in `tz`
+fn build_heatmap(
+    tz: Tz,
+    start: NaiveDate,
+    members: Vec<(i32, String)>,
+    windows: &[AvailabilityWindow],
+    exceptions: &[AvailabilityException],
+) -> Heatmap {
+    let mut times: HashMap<i32, MemberTimes> = members.into_iter()
+        .map(|(id, name)| (id, MemberTimes { name, ..Default::default() }))
+        .collect();
+    let total = times.len().max(1);
+
+    // Expand the weekly windows a day beyond the range so timezone offsets can't cut them off
+    for w in windows {
+        let (Some(member), Some(wtz)) = (times.get_mut(&w.user_id), parse_tz(&w.timezone)) else {
+            warn!("Skipping availability window {}", w.id);
+            continue;
+        };
+
+        for offset in -1..=HEATMAP_DAYS {
+            let day = start + Duration::days(offset);
+            if day.weekday().num_days_from_monday() as i32 == w.weekday {
+                member.available.push(interval(&wtz, day, w.start_time, w.end_time));
+            }
+        }
+    }
+
+    for e in exceptions {
+        let (Some(member), Some(etz)) = (times.get_mut(&e.user_id), parse_tz(&e.timezone)) else {
+            warn!("Skipping availability exception {}", e.id);
+            continue;
+        };
+
+        let i = interval(&etz, e.day, e.start_time, e.end_time);
+        if e.available {
+            member.available.push(i);
+        } else {
+            member.absent.push(i);
+        }
+    }
+
+    let mut members: Vec<MemberTimes> = times.into_values().collect();
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+    for m in members.iter_mut() {
+        m.available.sort();
+    }
+
+    let days: Vec<NaiveDate> = (0..HEATMAP_DAYS).map(|d| start + Duration::days(d)).collect();
+
+    let rows = (0..24 * 60 / SLOT_MINUTES).map(|slot| {
+        let offset = Duration::minutes(slot * SLOT_MINUTES);
+
+        HeatmapRow {
+            time: (NaiveTime::from_hms_opt(0, 0, 0).unwrap() + offset).format("%H:%M").to_string(),
+            cells: days.iter().map(|d| {
+                let local = d.and_hms_opt(0, 0, 0).unwrap() + offset;
+                let from = to_utc(&tz, local);
+                let to = to_utc(&tz, local + Duration::minutes(SLOT_MINUTES));
+
+                let names: Vec<String> = members.iter()
+                    .filter(|m| m.covers(from, to))
+                    .map(|m| m.name.clone())
+                    .collect();
+
+                HeatmapCell {
+                    intensity: names.len() as f64 / total as f64,
+                    names,
+                }
+            }).collect(),
+        }
+    }).collect();
+
+    Heatmap {
+        timezone: tz.name().to_string(),
+        days: days.iter().map(|d| d.format("%a %d.%m.").to_string()).collect(),
+        rows,
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(super) struct HeatmapQuery {
+    tz: Option<String>,
+}
+
+/// Loads the availability of every group member for the coming week.
+/// Uses the timezone from the query, or the one the viewer entered their availability in.
+pub(super) async fn load_heatmap(
+    repo: &dyn Repository,
+    conn: &mut AnyConnection,
+    gid: i32,
+    uid: i32,
+    query: &HeatmapQuery,
+) -> Result<Heatmap, RepoError> {
+    let members = repo.members(&mut *conn, gid).await?;
+    let windows = repo.group_windows(&mut *conn, gid).await?;
+    let exceptions = repo.group_exceptions(&mut *conn, gid, (Utc::now() - Duration::days(2)).date_naive()).await?;
+
+    let tz = query.tz.as_deref()
+        .or_else(|| windows.iter().rev().find(|w| w.user_id == uid).map(|w| w.timezone.as_str()))
+        .and_then(parse_tz)
+        .unwrap_or(Tz::UTC);
+
+    let start = Utc::now().with_timezone(&tz).date_naive();
+    let members = members.into_iter().map(|m| (m.id, m.username)).collect();
+
+    Ok(build_heatmap(tz, start, members, &windows, &exceptions))
+}
+
+#[derive(Serialize)]
+struct RenderableWindow {
+    id: i32,
+    weekday: &'static str,
+    start_time: String,
+    end_time: String,
+    timezone: String,
+}
+
+#[derive(Serialize)]
+struct RenderableException {
+    id: i32,
+    day: String,
+    start_time: String,
+    end_time: String,
+    available: bool,
+    timezone: String,
+}
+
+/// Renders the availability editor of a member with their current windows and exceptions
+async fn render_availability(
+    tera: &Tera,
+    repo: &dyn Repository,
+    conn: &mut AnyConnection,
+    gid: i32,
+    uid: i32,
+    errors: Vec<String>,
+    mut res: HttpResponseBuilder,
+) -> Result<HttpResponse, RepoError> {
+    let gname = repo.group_name(&mut *conn, gid).await?;
+    let windows = repo.member_windows(&mut *conn, gid, uid).await?;
+    let exceptions = repo.member_exceptions(&mut *conn, gid, uid, (Utc::now() - Duration::days(1)).date_naive()).await?;
+
+    let timezone = windows.iter().map(|w| w.timezone.clone())
+        .chain(exceptions.iter().map(|e| e.timezone.clone()))
+        .last();
+
+    let windows: Vec<RenderableWindow> = windows.into_iter().map(|w| RenderableWindow {
+        id: w.id,
+        weekday: WEEKDAYS.get(w.weekday as usize).copied().unwrap_or("?"),
+        start_time: w.start_time.format("%H:%M").to_string(),
+        end_time: w.end_time.format("%H:%M").to_string(),
+        timezone: w.timezone,
+    }).collect();
+
+    let exceptions: Vec<RenderableException> = exceptions.into_iter().map(|e| RenderableException {
+        id: e.id,
+        day: e.day.format("%a %d.%m.%Y").to_string(),
+        start_time: e.start_time.format("%H:%M").to_string(),
+        end_time: e.end_time.format("%H:%M").to_string(),
+        available: e.available,
+        timezone: e.timezone,
+    }).collect();
+
+    let mut con = Context::new();
+    con.insert("gname", &gname);
+    con.insert("group", &gid);
+    con.insert("windows", &windows);
+    con.insert("exceptions", &exceptions);
+    con.insert("weekdays", &WEEKDAYS);
+    con.insert("timezone", &timezone);
+    con.insert("timezones", &chrono_tz::TZ_VARIANTS.iter().map(|t| t.name()).collect::<Vec<_>>());
+    con.insert("errors", &errors);
+
+    Ok(res.body(tera.render("availability.html", &con).unwrap()))
+}
+
+#[get("/groups/{id}/availability")]
+async fn show_availability(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    gid: web::Path<(i32,)>,
+) -> impl Responder {
+    let gid = gid.0;
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    match repo.is_member(&mut trans, gid, id).await {
+        Ok(true) => (),
+        Ok(false) => return HttpResponse::Forbidden().body("You are not a group member"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let res = match render_availability(&tera, repo.as_ref(), &mut trans, gid, id, Vec::new(), HttpResponse::Ok()).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    res
+}
+
+#[derive(Deserialize)]
+struct WindowForm {
+    weekday: i32,
+    start: String,
+    end: String,
+    timezone: String,
+}
+
+#[derive(Deserialize)]
+struct ExceptionForm {
+    day: String,
+    start: String,
+    end: String,
+    available: bool,
+    timezone: String,
+}
+
+/// A new window or exception, validated
+enum NewEntry {
+    Window(WindowForm, NaiveTime, NaiveTime),
+    Exception(ExceptionForm, NaiveDate, NaiveTime, NaiveTime),
+}
+
+impl NewEntry {
+    fn window(form: WindowForm) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+
+        if !(0..7).contains(&form.weekday) {
+            errors.push("Unknown weekday".to_string());
+        }
+
+        let start = parse_time(&form.start).map_err(|e| errors.push(e)).ok();
+        let end = parse_time(&form.end).map_err(|e| errors.push(e)).ok();
+
+        if parse_tz(&form.timezone).is_none() {
+            errors.push(format!("Unknown timezone {}", form.timezone));
+        }
+
+        match (start, end) {
+            (Some(start), Some(end)) if errors.is_empty() => Ok(NewEntry::Window(form, start, end)),
+            _ => Err(errors),
+        }
+    }
+
+    fn exception(form: ExceptionForm) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+
+        let day = NaiveDate::parse_from_str(&form.day, "%Y-%m-%d")
+            .map_err(|_| errors.push(format!("Invalid date {}", form.day)))
+            .ok();
+        let start = parse_time(&form.start).map_err(|e| errors.push(e)).ok();
+        let end = parse_time(&form.end).map_err(|e| errors.push(e)).ok();
+
+        if parse_tz(&form.timezone).is_none() {
+            errors.push(format!("Unknown timezone {}", form.timezone));
+        }
+
+        match (day, start, end) {
+            (Some(day), Some(start), Some(end)) if errors.is_empty() => Ok(NewEntry::Exception(form, day, start, end)),
+            _ => Err(errors),
+        }
+    }
+
+    async fn insert(&self, repo: &dyn Repository, conn: &mut AnyConnection, gid: i32, uid: i32) -> Result<(), RepoError> {
+        match self {
+            NewEntry::Window(form, start, end) => repo.add_window(conn, &AvailabilityWindow {
+                id: 0,
+                group_id: gid,
+                user_id: uid,
+                weekday: form.weekday,
+                start_time: *start,
+                end_time: *end,
+                timezone: form.timezone.clone(),
+            }).await,
+            NewEntry::Exception(form, day, start, end) => repo.add_exception(conn, &AvailabilityException {
+                id: 0,
+                group_id: gid,
+                user_id: uid,
+                day: *day,
+                start_time: *start,
+                end_time: *end,
+                available: form.available,
+                timezone: form.timezone.clone(),
+            }).await,
+        }
+    }
+}
+
+/// Stores a new window or exception, or shows the editor again with the errors
+async fn add_entry(
+    tera: &Tera,
+    repo: &dyn Repository,
+    gid: i32,
+    id: i32,
+    entry: Result<NewEntry, Vec<String>>,
+) -> HttpResponse {
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    match repo.is_member(&mut trans, gid, id).await {
+        Ok(true) => (),
+        Ok(false) => return HttpResponse::Forbidden().body("You are not a group member"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let entry = match entry {
+        Ok(v) => v,
+        Err(errors) => {
+            return match render_availability(tera, repo, &mut trans, gid, id, errors, HttpResponse::BadRequest()).await {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("{:?}", e);
+                    HttpResponse::InternalServerError().body("Database error")
+                },
+            };
+        },
+    };
+
+    if let Err(e) = entry.insert(repo, &mut trans, gid, id).await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Database error");
+    }
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, format!("/auth/groups/{}/availability", gid))).finish()
+}
+
+#[post("/groups/{id}/availability/weekly")]
+async fn add_availability_window(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    gid: web::Path<(i32,)>,
+    form: web::Form<WindowForm>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    add_entry(&tera, repo.as_ref(), gid.0, id, NewEntry::window(form.into_inner())).await
+}
+
+#[post("/groups/{id}/availability/exception")]
+async fn add_availability_exception(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    gid: web::Path<(i32,)>,
+    form: web::Form<ExceptionForm>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    add_entry(&tera, repo.as_ref(), gid.0, id, NewEntry::exception(form.into_inner())).await
+}
+
+#[post("/groups/{id}/availability/{kind}/{aid}/delete")]
+async fn delete_availability(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    path: web::Path<(i32, String, i32)>,
+) -> impl Responder {
+    let (gid, kind, aid) = path.into_inner();
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    let res = match kind.as_str() {
+        "weekly" => repo.delete_window(&mut trans, gid, id, aid).await,
+        "exception" => repo.delete_exception(&mut trans, gid, id, aid).await,
+        _ => return HttpResponse::NotFound().finish(),
+    };
+
+    match res {
+        Ok(false) => return HttpResponse::Forbidden().body("This is not your availability"),
+        Ok(true) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, format!("/auth/groups/{}/availability", gid))).finish()
+}
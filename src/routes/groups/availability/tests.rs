@@ -0,0 +1,103 @@
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+
+use crate::data::{AvailabilityException, AvailabilityWindow};
+use super::{build_heatmap, Heatmap};
+
+fn time(h: u32, m: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(h, m, 0).unwrap()
+}
+
+fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+fn window(user_id: i32, weekday: i32, start: NaiveTime, end: NaiveTime, timezone: &str) -> AvailabilityWindow {
+    AvailabilityWindow { id: 0, group_id: 1, user_id, weekday, start_time: start, end_time: end, timezone: timezone.to_string() }
+}
+
+/// The hours of a day of the heatmap in which the member is available
+fn hours(heatmap: &Heatmap, day: usize, name: &str) -> Vec<usize> {
+    heatmap.rows.iter().enumerate()
+        .filter(|(_, r)| r.cells[day].names.iter().any(|n| n == name))
+        .map(|(h, _)| h)
+        .collect()
+}
+
+#[test]
+fn shows_windows_in_the_viewer_timezone() {
+    // Monday, Berlin is at UTC+1 and New York at UTC-5
+    let start = date(2024, 1, 15);
+    let members = vec![(1, "alice".to_string()), (2, "bob".to_string())];
+    let windows = [
+        window(1, 0, time(18, 0), time(21, 0), "Europe/Berlin"),
+        window(2, 0, time(17, 0), time(19, 0), "UTC"),
+    ];
+
+    let heatmap = build_heatmap(Tz::America__New_York, start, members, &windows, &[]);
+
+    assert_eq!(heatmap.timezone, "America/New_York");
+    assert_eq!(heatmap.days[0], "Mon 15.01.");
+    assert_eq!(hours(&heatmap, 0, "alice"), vec![12, 13, 14]);
+    assert_eq!(hours(&heatmap, 0, "bob"), vec![12, 13]);
+    assert!((1..7).all(|d| hours(&heatmap, d, "alice").is_empty()));
+
+    assert_eq!(heatmap.rows[12].cells[0].intensity, 1.0);
+    assert_eq!(heatmap.rows[14].cells[0].intensity, 0.5);
+    assert_eq!(heatmap.rows[15].cells[0].intensity, 0.0);
+}
+
+#[test]
+fn keeps_local_windows_across_a_dst_switch() {
+    // Berlin switches to summer time on Sunday the 31st at 02:00
+    let start = date(2024, 3, 25);
+    let members = vec![(1, "alice".to_string())];
+    let windows: Vec<_> = (0..7).map(|d| window(1, d, time(20, 0), time(22, 0), "Europe/Berlin")).collect();
+
+    let utc = build_heatmap(Tz::UTC, start, members.clone(), &windows, &[]);
+    assert_eq!(hours(&utc, 5, "alice"), vec![19, 20], "Saturday is still winter time");
+    assert_eq!(hours(&utc, 6, "alice"), vec![18, 19], "Sunday is summer time");
+
+    let berlin = build_heatmap(Tz::Europe__Berlin, start, members, &windows, &[]);
+    assert!((0..7).all(|d| hours(&berlin, d, "alice") == vec![20, 21]));
+}
+
+#[test]
+fn moves_utc_windows_with_the_viewer_dst_switch() {
+    let start = date(2024, 3, 25);
+    let members = vec![(1, "alice".to_string())];
+    let windows = [
+        window(1, 5, time(18, 0), time(19, 0), "UTC"),
+        window(1, 6, time(18, 0), time(19, 0), "UTC"),
+    ];
+
+    let heatmap = build_heatmap(Tz::Europe__Berlin, start, members, &windows, &[]);
+
+    assert_eq!(hours(&heatmap, 5, "alice"), vec![19]);
+    assert_eq!(hours(&heatmap, 6, "alice"), vec![20]);
+    // The skipped hour is still a row, but nobody can be available in it
+    assert!(heatmap.rows[2].cells[6].names.is_empty());
+}
+
+#[test]
+fn exceptions_add_and_remove_time() {
+    let start = date(2024, 1, 15);
+    let members = vec![(1, "alice".to_string())];
+    let windows = [window(1, 1, time(18, 0), time(22, 0), "Europe/Berlin")];
+    let exceptions = [
+        AvailabilityException {
+            id: 0, group_id: 1, user_id: 1, day: date(2024, 1, 16), start_time: time(19, 0), end_time: time(20, 0),
+            available: false, timezone: "Europe/Berlin".to_string(),
+        },
+        AvailabilityException {
+            id: 0, group_id: 1, user_id: 1, day: date(2024, 1, 20), start_time: time(23, 0), end_time: time(1, 0),
+            available: true, timezone: "Europe/Berlin".to_string(),
+        },
+    ];
+
+    let heatmap = build_heatmap(Tz::Europe__Berlin, start, members, &windows, &exceptions);
+
+    assert_eq!(hours(&heatmap, 1, "alice"), vec![18, 20, 21]);
+    assert_eq!(hours(&heatmap, 5, "alice"), vec![23], "the exception wraps into Sunday");
+    assert_eq!(hours(&heatmap, 6, "alice"), vec![0]);
+}
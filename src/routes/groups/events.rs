@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use actix_session::Session;
+use actix_web::{post, web, HttpResponse, Responder, http::header};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use log::error;
+use serde::{Deserialize, Serialize};
+use sqlx::AnyConnection;
+
+use crate::data::{GroupEvent, Raid};
+use crate::repo::{RepoError, Repository};
+use super::availability::{parse_tz, to_utc};
+
+const DURATION_MAX: i32 = 24 * 60;
+const NOTE_MAX: usize = 255;
+
+#[derive(Serialize, Debug)]
+struct RenderableEvent {
+    id: i32,
+    raid: String,
+    starts_at: String,
+    duration: i32,
+    note: String,
+    can_delete: bool,
+    roster: Vec<EventChar>,
+}
+
+#[derive(Serialize, Debug)]
+struct EventChar {
+    id: i32,
+    name: String,
+    class: String,
+    item_level: i32,
+    username: String,
+    own: bool,
+}
+
+/// An entry of a select box
+#[derive(Serialize, Debug)]
+struct Choice {
+    id: i32,
+    name: String,
+}
+
+/// The upcoming raids of a group and what the viewer needs to schedule or join them
+#[derive(Serialize, Debug)]
+pub(super) struct Schedule {
+    events: Vec<RenderableEvent>,
+    raids: Vec<Choice>,
+    chars: Vec<Choice>,
+}
+
+/// Loads the raids of a group that did not end yet, with times shown in `tz`
+pub(super) async fn load_schedule(
+    repo: &dyn Repository,
+    conn: &mut AnyConnection,
+    gid: i32,
+    uid: i32,
+    tz: Tz,
+    raids: &[Raid],
+) -> Result<Schedule, RepoError> {
+    let now = Utc::now();
+    // No event runs longer than this, the ones that ended are dropped below
+    let since = now - Duration::minutes(DURATION_MAX as i64);
+
+    let owner = repo.group_owner(&mut *conn, gid).await?.ok_or(sqlx::Error::RowNotFound)?;
+
+    let events: Vec<_> = repo.group_events(&mut *conn, gid, since).await?
+        .into_iter()
+        .filter(|e| e.starts_at + Duration::minutes(e.duration as i64) >= now)
+        .collect();
+
+    let mut roster: HashMap<i32, Vec<EventChar>> = HashMap::new();
+
+    for c in repo.group_event_characters(&mut *conn, gid, since).await? {
+        roster.entry(c.event_id).or_default().push(EventChar {
+            id: c.id,
+            name: c.name,
+            class: c.class,
+            item_level: c.item_level,
+            username: c.username,
+            own: c.user_id == uid,
+        });
+    }
+
+    let raids = raids.iter()
+        .map(|r| Choice { id: r.id, name: format!("{} {}", r.name, r.difficulty) })
+        .collect();
+
+    let chars = repo.characters(&mut *conn, uid).await?
+        .into_iter()
+        .map(|c| Choice { id: c.id, name: c.name })
+        .collect();
+
+    Ok(Schedule {
+        events: events.into_iter().map(|e| RenderableEvent {
+            id: e.id,
+            raid: format!("{} {}", e.raid, e.difficulty),
+            starts_at: e.starts_at.with_timezone(&tz).format("%a %d.%m. %H:%M").to_string(),
+            duration: e.duration,
+            note: e.note,
+            can_delete: e.creator_id == uid || owner == uid,
+            roster: roster.remove(&e.id).unwrap_or_default(),
+        }).collect(),
+        raids,
+        chars,
+    })
+}
+
+#[derive(Deserialize)]
+struct EventForm {
+    raid_id: i32,
+    /// Local time as sent by a `datetime-local` input
+    start: String,
+    timezone: String,
+    /// In minutes
+    duration: i32,
+    note: String,
+}
+
+impl EventForm {
+    fn starts_at(&self) -> Result<DateTime<Utc>, String> {
+        let tz = parse_tz(&self.timezone).ok_or_else(|| format!("Unknown timezone {}", self.timezone))?;
+        let local = NaiveDateTime::parse_from_str(&self.start, "%Y-%m-%dT%H:%M")
+            .map_err(|_| format!("Invalid start {}", self.start))?;
+
+        Ok(to_utc(&tz, local))
+    }
+}
+
+fn redirect(gid: i32) -> HttpResponse {
+    HttpResponse::SeeOther().insert_header((header::LOCATION, format!("/auth/groups/{}", gid))).finish()
+}
+
+#[post("/groups/{id}/events")]
+async fn create_event(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    gid: web::Path<(i32,)>,
+    form: web::Form<EventForm>,
+) -> impl Responder {
+    let gid = gid.0;
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let starts_at = match form.starts_at() {
+        Ok(v) => v,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    if !(1..=DURATION_MAX).contains(&form.duration) {
+        return HttpResponse::BadRequest().body(format!("Duration must be between 1 and {} minutes", DURATION_MAX));
+    }
+
+    if form.note.chars().count() > NOTE_MAX {
+        return HttpResponse::BadRequest().body(format!("Note can be at most {} characters", NOTE_MAX));
+    }
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    match repo.is_member(&mut trans, gid, id).await {
+        Ok(true) => (),
+        Ok(false) => return HttpResponse::Forbidden().body("You are not a group member"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let event = GroupEvent {
+        id: 0,
+        group_id: gid,
+        raid_id: form.raid_id,
+        creator_id: id,
+        starts_at,
+        duration: form.duration,
+        note: form.note.trim().to_string(),
+        created_at: Utc::now(),
+    };
+
+    match repo.create_event(&mut trans, &event).await {
+        Ok(false) => return HttpResponse::BadRequest().body("Unknown raid"),
+        Ok(true) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    redirect(gid)
+}
+
+#[derive(Deserialize)]
+struct JoinForm {
+    character_id: i32,
+}
+
+#[post("/groups/{id}/events/{eid}/join")]
+async fn join_event(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    path: web::Path<(i32, i32)>,
+    form: web::Form<JoinForm>,
+) -> impl Responder {
+    let (gid, eid) = path.into_inner();
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    // Only members can add their own characters to events of the group
+    match repo.join_event(&mut trans, gid, eid, id, form.character_id).await {
+        Ok(_) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    redirect(gid)
+}
+
+#[post("/groups/{id}/events/{eid}/leave/{cid}")]
+async fn leave_event(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    path: web::Path<(i32, i32, i32)>,
+) -> impl Responder {
+    let (gid, eid, cid) = path.into_inner();
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    match repo.leave_event(&mut trans, gid, eid, id, cid).await {
+        Ok(_) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    redirect(gid)
+}
+
+#[post("/groups/{id}/events/{eid}/delete")]
+async fn delete_event(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    path: web::Path<(i32, i32)>,
+) -> impl Responder {
+    let (gid, eid) = path.into_inner();
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    // The creator of an event and the owner of the group can remove it
+    match repo.delete_event(&mut trans, gid, eid, id).await {
+        Ok(false) => return HttpResponse::Forbidden().body("You can not remove this event"),
+        Ok(true) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    redirect(gid)
+}
@@ -0,0 +1,97 @@
+use actix_session::Session;
+use actix_web::{get, web, Either, HttpResponse, Responder};
+use actix_web_lab::sse::{ChannelStream, Sse};
+use log::error;
+use tera::{Tera, Context};
+
+use crate::game_data::GameData;
+use crate::live::Broadcaster;
+use crate::repo::Repository;
+use super::matrix::load_matrix;
+use super::view::GroupView;
+
+/// Server-Sent Events with every change to the group, so open group pages can refresh
+#[get("/groups/{id}/live")]
+async fn live_updates(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    live: web::Data<Broadcaster>,
+    gid: web::Path<(i32,)>,
+) -> Either<HttpResponse, Sse<ChannelStream>> {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+    let gid = gid.0;
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    match repo.is_member(&mut trans, gid, id).await {
+        Ok(true) => (),
+        Ok(false) => return Either::Left(HttpResponse::Forbidden().body("You are not a group member")),
+        Err(e) => {
+            error!("{:?}", e);
+            return Either::Left(HttpResponse::InternalServerError().body("Database error"));
+        },
+    };
+
+    if let Err(e) = trans.commit().await {
+        error!("{:?}", e);
+        return Either::Left(HttpResponse::InternalServerError().body("Database error"));
+    }
+
+    Either::Right(live.subscribe(gid))
+}
+
+/// Only the raid matrix of the group page, in the view the member saved
+#[get("/groups/{id}/matrix")]
+async fn show_matrix(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    gid: web::Path<(i32,)>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+    let gid = gid.0;
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    match repo.is_member(&mut trans, gid, id).await {
+        Ok(true) => (),
+        Ok(false) => return HttpResponse::Forbidden().body("You are not a group member"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let view = match GroupView::load(repo.as_ref(), &mut trans, gid, id).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let matrix = match load_matrix(repo.as_ref(), &mut trans, gid, &game_data.get().rules, &view).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    if let Err(e) = trans.commit().await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Database error");
+    }
+
+    let mut con = Context::new();
+    con.insert("matrix", &matrix);
+
+    HttpResponse::Ok().body(tera.render("group_matrix.html", &con).unwrap())
+}
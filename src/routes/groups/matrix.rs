@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use sqlx::AnyConnection;
+
+use crate::repo::{RepoError, Repository};
+use crate::rules::{self, RaidRules};
+use super::view::GroupView;
+
+#[derive(Serialize, Debug)]
+pub(super) struct RaidColumn {
+    id: i32,
+    name: String,
+    obsolete: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct RenderableMember {
+    name: String,
+    build: String,
+    main: bool,
+    combat_power: i32,
+    stats: String,
+    card_set: String,
+    note: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+struct RenderableRaid {
+    id: i32,
+    name: String,
+    dd: Vec<RenderableMember>,
+    dd_nogold: Vec<RenderableMember>,
+    support: Vec<RenderableMember>,
+    support_nogold: Vec<RenderableMember>,
+}
+
+impl RenderableRaid {
+    fn eligible(&self) -> usize {
+        self.dd.len() + self.dd_nogold.len() + self.support.len() + self.support_nogold.len()
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+struct RenderableUser {
+    name: String,
+    raids: Vec<RenderableRaid>,
+}
+
+/// Which characters of every member can still do which raid, as shown on the group page
+#[derive(Serialize, Debug)]
+pub(super) struct Matrix {
+    /// Every raid, to pick the shown columns from
+    raids: Vec<RaidColumn>,
+    /// The raids that are shown
+    columns: Vec<RaidColumn>,
+    users: Vec<RenderableUser>,
+}
+
+/// Builds the raid matrix of a group. The number of queries does not depend on the size of the group.
+pub(super) async fn load_matrix(
+    repo: &dyn Repository,
+    conn: &mut AnyConnection,
+    gid: i32,
+    rules: &RaidRules,
+    view: &GroupView,
+) -> Result<Matrix, RepoError> {
+    let members = repo.members(&mut *conn, gid).await?;
+    let chars = repo.group_characters(&mut *conn, gid).await?;
+    let clears = repo.clears_of_group(conn, gid).await?;
+    let clears = rules::clears_by_character(&clears);
+
+    let mut users = Vec::new();
+    let mut positions: HashMap<i32, usize> = HashMap::new();
+
+    for m in members.iter().filter(|m| view.matches_member(&m.username)) {
+        positions.insert(m.id, users.len());
+        users.push(RenderableUser {
+            name: m.username.clone(),
+            raids: rules.raids.iter().map(|r| RenderableRaid {
+                id: r.id,
+                name: format!("{} {}", r.name, r.difficulty),
+                ..Default::default()
+            }).collect(),
+        });
+    }
+
+    for c in chars.iter().filter(|c| view.matches(&c.build, c.main)) {
+        let Some(&pos) = positions.get(&c.user_id) else { continue };
+
+        let states = rules.states(c.item_level, c.gold_earner, clears.get(&c.id).map(Vec::as_slice).unwrap_or_default());
+
+        for (raid, state) in users[pos].raids.iter_mut().zip(states) {
+            if !state.eligible {
+                continue;
+            }
+
+            let member = RenderableMember {
+                name: c.name.clone(),
+                build: c.build.clone(),
+                main: c.main,
+                combat_power: c.combat_power,
+                stats: c.stats.clone(),
+                card_set: c.card_set.clone(),
+                note: c.note.clone(),
+            };
+
+            match (c.support == 1, state.gives_gold) {
+                (true, true) => raid.support.push(member),
+                (false, true) => raid.dd.push(member),
+                (true, false) => raid.support_nogold.push(member),
+                (false, false) => raid.dd_nogold.push(member),
+            };
+        }
+    }
+
+    // Raids are in the same order for every member, so columns can be picked by position
+    let shown: Vec<bool> = rules.raids.iter().enumerate().map(|(i, r)| {
+        let eligible: usize = users.iter().map(|u| u.raids[i].eligible()).sum();
+
+        !view.hidden_raids.contains(&r.id)
+            && (!view.hide_obsolete || !r.obsolete)
+            && eligible >= view.min_eligible as usize
+    }).collect();
+
+    for u in users.iter_mut() {
+        let mut i = 0;
+        u.raids.retain(|_| {
+            i += 1;
+            shown[i - 1]
+        });
+    }
+
+    if view.sort == "available" {
+        users.sort_by_key(|u| std::cmp::Reverse(u.raids.iter().map(RenderableRaid::eligible).sum::<usize>()));
+    }
+
+    let raids: Vec<RaidColumn> = rules.raids.iter().map(|r| RaidColumn {
+        id: r.id,
+        name: format!("{} {}", r.name, r.difficulty),
+        obsolete: r.obsolete,
+    }).collect();
+
+    let columns = raids.iter().zip(shown.iter())
+        .filter(|(_, s)| **s)
+        .map(|(r, _)| RaidColumn { id: r.id, name: r.name.clone(), obsolete: r.obsolete })
+        .collect();
+
+    Ok(Matrix { raids, columns, users })
+}
@@ -0,0 +1,384 @@
+mod availability;
+mod events;
+mod live;
+mod matrix;
+mod view;
+#[cfg(test)]
+mod tests;
+
+use actix_session::Session;
+use actix_web::{get, post, HttpResponse, web, Responder, http::header};
+use log::{error, warn};
+use tera::{Tera, Context};
+use crate::game_data::GameData;
+use crate::live::{Broadcaster, Change};
+use crate::repo::Repository;
+use serde::{Deserialize, Serialize};
+use availability::HeatmapQuery;
+use view::{GroupView, SORTS};
+pub use availability::*;
+pub use events::*;
+pub use live::*;
+
+#[get("/groups/{id}")]
+async fn view_group(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    gid: web::Path<(i32,)>,
+    query: web::Query<Vec<(String, String)>>,
+    heatmap: web::Query<HeatmapQuery>,
+) -> impl Responder {
+    let gid = gid.0;
+    let session = session.get::<i32>("id").unwrap();
+    
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    let gname = match repo.member_group_name(&mut trans, gid, session.unwrap()).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let gname = match gname {
+        None => return HttpResponse::Forbidden().body("You are not a group member"),
+        Some(v) => v,
+    };
+
+    let data = game_data.get();
+    let rules = &data.rules;
+
+    let raid_ids: Vec<i32> = rules.raids.iter().map(|r| r.id).collect();
+
+    let view = match GroupView::parse(&query, &raid_ids) {
+        Some(v) => v.save(repo.as_ref(), &mut trans, gid, session.unwrap()).await.map(|_| v),
+        None => GroupView::load(repo.as_ref(), &mut trans, gid, session.unwrap()).await,
+    };
+
+    let view = match view {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let matrix = match matrix::load_matrix(repo.as_ref(), &mut trans, gid, rules, &view).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let heatmap = match availability::load_heatmap(repo.as_ref(), &mut trans, gid, session.unwrap(), &heatmap).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let schedule = match events::load_schedule(repo.as_ref(), &mut trans, gid, session.unwrap(), heatmap.tz(), &rules.raids).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    let mut con = Context::new();
+    con.insert("gid", &gid);
+    con.insert("gname", &gname);
+    con.insert("matrix", &matrix);
+    con.insert("view", &view);
+    con.insert("sorts", &SORTS);
+    con.insert("heatmap", &heatmap);
+    con.insert("schedule", &schedule);
+
+    HttpResponse::Ok().body(tera.render("view_group.html", &con).unwrap())
+}
+
+#[get("/me/groups")]
+async fn view_groups(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let groups = match repo.groups_of(id).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let mut con = Context::new();
+    con.insert("user_id", &id);
+    con.insert("groups", &groups);
+
+    HttpResponse::Ok().body(tera.render("view_groups.html", &con).unwrap())
+}
+
+#[get("/me/invites")]
+async fn invites(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let invites = match repo.pending_invites(id).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let mut con = Context::new();
+    con.insert("invites", &invites);
+
+    HttpResponse::Ok().body(tera.render("invites.html", &con).unwrap())
+}
+
+#[get("/me/invites/accept/{id}")]
+async fn accept_invite(
+    repo: web::Data<dyn Repository>,
+    live: web::Data<Broadcaster>,
+    session: Session,
+    iid: web::Path<(i32, )>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let invite = match repo.accept_invite(iid.0, id).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return HttpResponse::Forbidden().body("This is not a pending invite that can be accepted"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    live.publish(invite.group_id, Change::Member { user_id: invite.dest });
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, "../../invites")).finish()
+}
+
+#[get("/me/invites/decline/{id}")]
+async fn decline_invite(
+    repo: web::Data<dyn Repository>,
+    session: Session,
+    iid: web::Path<(i32, )>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    match repo.decline_invite(iid.0, id).await {
+        Ok(true) => HttpResponse::SeeOther().insert_header((header::LOCATION, "../../invites")).finish(),
+        Ok(false) => HttpResponse::Forbidden().body("This is not a pending invite that can be accepted"),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().body("Database error")
+        },
+    }
+}
+
+#[get("/me/groups/new")]
+async fn create_group(
+    tera: web::Data<Tera>,
+) -> impl Responder {
+    HttpResponse::Ok().body(tera.render("create_group.html", &Context::new()).unwrap())
+}
+
+#[derive(Deserialize)]
+struct NameForm {
+    name: String,
+}
+
+#[post("/me/groups/new")]
+async fn create_group_post(
+    repo: web::Data<dyn Repository>,
+    session: Session,
+    name: web::Form<NameForm>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    if let Err(e) = repo.create_group(&name.name, id).await {
+        error!("{:?}", e);
+        return HttpResponse::InternalServerError().body("Database error");
+    }
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, "/auth/me/groups")).finish()
+}
+
+#[post("/me/groups/edit/{id}/invite")]
+async fn invite_group(
+    repo: web::Data<dyn Repository>,
+    session: Session,
+    group_id: web::Path<(i32,)>,
+    name: web::Form<NameForm>,
+) -> impl Responder {
+    let session = session.get::<i32>("id").unwrap().unwrap();
+
+    let group_id = group_id.0;
+
+    let mut trans = match repo.begin().await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match repo.group_owner(&mut trans, group_id).await {
+        Ok(Some(owner)) if owner == session => (),
+        Ok(_) => return HttpResponse::Forbidden().body("Only the owner can invite people"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match repo.invite(&mut trans, session, group_id, &name.name).await {
+        Ok(_) => (),
+        Err(e) => {
+            warn!("{:?}", e);
+            return HttpResponse::BadRequest().body(format!("Could not invite {}", name.name));
+        },
+    }
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, format!("../{}", group_id))).finish()
+}
+
+#[get("/me/groups/edit/{id}")]
+async fn edit_group(
+    repo: web::Data<dyn Repository>,
+    session: Session,
+    group_id: web::Path<(i32,)>,
+    tera: web::Data<Tera>,
+) -> impl Responder {
+    let mut trans = match repo.begin().await {
+        Ok(v) => v,
+        Err(_) => return HttpResponse::InternalServerError().body("Failed to connect to database"),
+    };
+
+    let session = session.get::<i32>("id").unwrap().unwrap();
+
+    match repo.group_owner(&mut trans, group_id.0).await {
+        Ok(Some(owner)) if owner == session => (),
+        Ok(_) => return HttpResponse::Forbidden().body("Only the owner can access the admin informations"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct RenderableGroupMember {
+        id: i32,
+        name: String,
+    }
+
+    let members: Vec<RenderableGroupMember> = match repo.members(&mut trans, group_id.0).await {
+        Ok(v) => v.into_iter().map(|m| RenderableGroupMember { id: m.id, name: m.username }).collect(),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let group_name = match repo.group_name(&mut trans, group_id.0).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let mut con = Context::new();
+    con.insert("members", &members);
+    con.insert("gname", &group_name);
+    con.insert("group", &group_id.0);
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    HttpResponse::Ok().body(tera.render("edit_group.html", &con).unwrap())
+}
+
+#[get("/me/groups/remove/{gid}/{uid}")]
+async fn remove_user(
+    repo: web::Data<dyn Repository>,
+    live: web::Data<Broadcaster>,
+    session: Session,
+    vals: web::Path<(i32, i32, )>,
+) -> impl Responder {
+    let (gid, uid) = vals.into_inner();
+
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match repo.group_owner(&mut trans, gid).await {
+        Ok(Some(owner)) if owner == id => (),
+        Ok(_) => return HttpResponse::Forbidden().body("Only the owner can access the admin informations"),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match repo.remove_member(&mut trans, gid, uid).await {
+        Ok(_) => (),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    }
+    
+    match trans
+        .commit()
+        .await {
+            Ok(_) => (),
+            Err(e) => {
+                error!("{:?}", e);
+                return HttpResponse::InternalServerError().body("Database error");
+            },
+        }
+
+    live.publish(gid, Change::Member { user_id: uid });
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, format!("../../edit/{}", gid))).body("Not yet implemented")
+}
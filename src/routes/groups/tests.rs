@@ -0,0 +1,280 @@
+//! Query count benchmarks for the roster and group pages, and tests of the group handlers.
+//!
+//! Everything runs against a database of its own from [`crate::testing`]. The benchmarks seed
+//! users and groups of each size and count the statements of a page with [`CountingRepo`].
+
+use actix_web::http::StatusCode;
+
+use crate::game_data::GameData;
+use crate::repo::{CountingRepo, Repository};
+use crate::routes::load_characters_page;
+use crate::rules::Clear;
+use crate::testing::TestApp;
+use super::matrix::load_matrix;
+use super::view::{GroupView, SORTS};
+
+const SIZES: [usize; 3] = [1, 8, 32];
+const ALTS: usize = 6;
+
+/// Creates a user with `chars` characters that all cleared the first raid and returns their id
+async fn seed_user(repo: &dyn Repository, name: &str, chars: usize) -> i32 {
+    let class = repo.classes().await.unwrap()[0].id;
+    let raid = repo.raids().await.unwrap()[0].id;
+    let uid = repo.create_user(name, "").await.unwrap();
+
+    let mut trans = repo.begin().await.unwrap();
+    for a in 0..chars {
+        let cid = repo.add_character_in(&mut trans, uid, &format!("alt{}", a), class, 1400 + 20 * (a % ALTS) as i32).await.unwrap();
+        repo.add_clear(&mut trans, uid, &Clear { character_id: cid, raid_id: raid, took_gold: false }).await.unwrap();
+    }
+    trans.commit().await.unwrap();
+
+    uid
+}
+
+/// Creates a group of `members` users with `ALTS` characters each and returns its id
+async fn seed_group(repo: &dyn Repository, members: usize) -> i32 {
+    let mut users = Vec::new();
+    for m in 0..members {
+        users.push(seed_user(repo, &format!("bench-{}-{}", members, m), ALTS).await);
+    }
+
+    let gid = repo.create_group("bench", users[0]).await.unwrap();
+
+    for uid in users.iter().skip(1) {
+        sqlx::query("INSERT INTO group_members (group_id, user_id) VALUES (?, ?)")
+            .bind(gid)
+            .bind(uid)
+            .execute(repo.pool())
+            .await
+            .unwrap();
+    }
+
+    gid
+}
+
+#[actix_web::test]
+async fn roster_page_query_count_is_constant() {
+    let app = TestApp::spawn().await;
+    let data = GameData::load(app.repo.as_ref()).await.unwrap().get();
+    let repo = CountingRepo::new(app.repo.clone());
+    let mut counts = Vec::new();
+
+    for size in SIZES {
+        let uid = seed_user(&repo, &format!("bench-{}", size), size * ALTS).await;
+
+        let mut trans = repo.begin().await.unwrap();
+        let before = repo.statements();
+        load_characters_page(&repo, &mut trans, uid, &data.rules).await.unwrap();
+        counts.push(repo.statements() - before);
+        trans.commit().await.unwrap();
+    }
+
+    assert!(counts.windows(2).all(|w| w[0] == w[1]), "query counts differ: {:?}", counts);
+}
+
+#[actix_web::test]
+async fn group_page_query_count_is_constant() {
+    let app = TestApp::spawn().await;
+    let data = GameData::load(app.repo.as_ref()).await.unwrap().get();
+    let repo = CountingRepo::new(app.repo.clone());
+    let view = GroupView { sort: SORTS[1].to_string(), ..Default::default() };
+    let mut counts = Vec::new();
+
+    for size in SIZES {
+        let gid = seed_group(&repo, size).await;
+
+        let mut trans = repo.begin().await.unwrap();
+        let before = repo.statements();
+        load_matrix(&repo, &mut trans, gid, &data.rules, &view).await.unwrap();
+        counts.push(repo.statements() - before);
+        trans.commit().await.unwrap();
+    }
+
+    assert!(counts.windows(2).all(|w| w[0] == w[1]), "query counts differ: {:?}", counts);
+}
+
+async fn invite_exists(app: &TestApp, iid: i32) -> bool {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM invites WHERE id = ?")
+        .bind(iid)
+        .fetch_one(app.pool())
+        .await
+        .unwrap() > 0
+}
+
+#[actix_web::test]
+async fn create_group_form_renders() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+
+    let res = app.get_as(&alice, "/auth/me/groups/new").await;
+
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("<form"));
+}
+
+#[actix_web::test]
+async fn create_group_post_makes_creator_member() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+
+    let res = app.post_as(&alice, "/auth/me/groups/new", &[("name", "Static")]).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert_eq!(res.location(), Some("/auth/me/groups"));
+
+    let gid = app.create_group(&alice, "Second").await;
+    assert!(app.is_member(gid, alice.id).await);
+}
+
+#[actix_web::test]
+async fn view_groups_lists_memberships() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let gid = app.create_group(&alice, "Static").await;
+    app.create_group(&bob, "Other").await;
+
+    let res = app.get_as(&alice, "/auth/me/groups").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("Static"));
+    assert!(!res.body.contains("Other"));
+
+    app.join(&alice, gid, &bob).await;
+    let res = app.get_as(&bob, "/auth/me/groups").await;
+    assert!(res.body.contains("Static"));
+    assert!(res.body.contains("Other"));
+}
+
+#[actix_web::test]
+async fn view_group_is_for_members() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let gid = app.create_group(&alice, "Static").await;
+    app.add_character(&alice, "Alpha", 1620).await;
+
+    let res = app.get_as(&alice, &format!("/auth/groups/{}", gid)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("Static"));
+    assert!(res.body.contains("Alpha"));
+
+    let res = app.get_as(&bob, &format!("/auth/groups/{}", gid)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn edit_group_is_for_owner() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let gid = app.create_group(&alice, "Static").await;
+    app.join(&alice, gid, &bob).await;
+
+    let res = app.get_as(&alice, &format!("/auth/me/groups/edit/{}", gid)).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("Edit Group Static"));
+    assert!(res.body.contains("bob"));
+
+    let res = app.get_as(&bob, &format!("/auth/me/groups/edit/{}", gid)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn invite_group_is_for_owner() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    app.user("carol").await;
+    let gid = app.create_group(&alice, "Static").await;
+
+    let res = app.post_as(&alice, &format!("/auth/me/groups/edit/{}/invite", gid), &[("name", "bob")]).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert_eq!(res.location(), Some(format!("../{}", gid).as_str()));
+
+    let res = app.post_as(&bob, &format!("/auth/me/groups/edit/{}/invite", gid), &[("name", "carol")]).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    let invited: Vec<i32> = sqlx::query_scalar("SELECT dest FROM invites WHERE group_id = ?")
+        .bind(gid)
+        .fetch_all(app.pool())
+        .await
+        .unwrap();
+    assert_eq!(invited, vec![bob.id]);
+}
+
+#[actix_web::test]
+async fn invites_lists_pending_invites() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let gid = app.create_group(&alice, "Static").await;
+    let iid = app.invite(&alice, gid, &bob).await;
+
+    let res = app.get_as(&bob, "/auth/me/invites").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("Static"));
+    assert!(res.body.contains("Invited from alice"));
+    assert!(res.body.contains(&format!("invites/accept/{}", iid)));
+
+    let res = app.get_as(&alice, "/auth/me/invites").await;
+    assert!(!res.body.contains("Static"));
+}
+
+#[actix_web::test]
+async fn accept_invite_adds_member() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let carol = app.user("carol").await;
+    let gid = app.create_group(&alice, "Static").await;
+    let iid = app.invite(&alice, gid, &bob).await;
+
+    let res = app.get_as(&carol, &format!("/auth/me/invites/accept/{}", iid)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert!(!app.is_member(gid, carol.id).await);
+
+    let res = app.get_as(&bob, &format!("/auth/me/invites/accept/{}", iid)).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert_eq!(res.location(), Some("../../invites"));
+    assert!(app.is_member(gid, bob.id).await);
+    assert!(!invite_exists(&app, iid).await);
+
+    let res = app.get_as(&bob, &format!("/auth/me/invites/accept/{}", iid)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN, "an invite can only be used once");
+}
+
+#[actix_web::test]
+async fn decline_invite_removes_invite() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let gid = app.create_group(&alice, "Static").await;
+    let iid = app.invite(&alice, gid, &bob).await;
+
+    let res = app.get_as(&alice, &format!("/auth/me/invites/decline/{}", iid)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert!(invite_exists(&app, iid).await);
+
+    let res = app.get_as(&bob, &format!("/auth/me/invites/decline/{}", iid)).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert!(!invite_exists(&app, iid).await);
+    assert!(!app.is_member(gid, bob.id).await);
+}
+
+#[actix_web::test]
+async fn remove_user_is_for_owner() {
+    let app = TestApp::spawn().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let gid = app.create_group(&alice, "Static").await;
+    app.join(&alice, gid, &bob).await;
+
+    let res = app.get_as(&bob, &format!("/auth/me/groups/remove/{}/{}", gid, alice.id)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert!(app.is_member(gid, alice.id).await);
+
+    let res = app.get_as(&alice, &format!("/auth/me/groups/remove/{}/{}", gid, bob.id)).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert_eq!(res.location(), Some(format!("../../edit/{}", gid).as_str()));
+    assert!(!app.is_member(gid, bob.id).await);
+}
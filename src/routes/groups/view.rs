@@ -0,0 +1,98 @@
+use serde::Serialize;
+use sqlx::AnyConnection;
+
+use crate::repo::{GroupViewRecord, RepoError, Repository};
+
+/// How a member wants to see the raid matrix of a group. Submitted as query parameters and
+/// remembered per group, so the next visit without parameters looks the same.
+#[derive(Serialize, Debug, Default)]
+pub(super) struct GroupView {
+    /// Only show characters whose build contains this text
+    pub build: String,
+    /// Only show characters flagged as main
+    pub mains: bool,
+    /// Only show members whose name contains this text
+    pub member: String,
+    /// Hide raids flagged as obsolete
+    pub hide_obsolete: bool,
+    /// Only show raids at least this many characters of the group can still do
+    pub min_eligible: i32,
+    /// `name` or `available`
+    pub sort: String,
+    /// Raid columns the member unchecked
+    pub hidden_raids: Vec<i32>,
+}
+
+pub(super) const SORTS: [&str; 2] = ["name", "available"];
+
+impl GroupView {
+    /// Reads the view from the query. Returns `None` if the view form was not submitted.
+    pub fn parse(pairs: &[(String, String)], raid_ids: &[i32]) -> Option<Self> {
+        if !pairs.iter().any(|(k, _)| k == "view") {
+            return None;
+        }
+
+        let mut view = GroupView { sort: SORTS[0].to_string(), ..Default::default() };
+        let mut shown = Vec::new();
+
+        for (k, v) in pairs {
+            match k.as_str() {
+                "build" => view.build = v.trim().to_string(),
+                "mains" => view.mains = v == "true",
+                "member" => view.member = v.trim().to_string(),
+                "hide_obsolete" => view.hide_obsolete = v == "true",
+                "min_eligible" => view.min_eligible = v.parse().unwrap_or(0).max(0),
+                "sort" if SORTS.contains(&v.as_str()) => view.sort = v.clone(),
+                "raid" => shown.extend(v.parse::<i32>().ok()),
+                _ => (),
+            }
+        }
+
+        view.hidden_raids = raid_ids.iter().copied().filter(|r| !shown.contains(r)).collect();
+
+        Some(view)
+    }
+
+    pub fn matches(&self, build: &str, main: bool) -> bool {
+        if self.mains && !main {
+            return false;
+        }
+
+        self.build.is_empty() || build.to_lowercase().contains(&self.build.to_lowercase())
+    }
+
+    pub fn matches_member(&self, name: &str) -> bool {
+        self.member.is_empty() || name.to_lowercase().contains(&self.member.to_lowercase())
+    }
+
+    /// Loads the view a member saved for a group, or the default view
+    pub async fn load(repo: &dyn Repository, conn: &mut AnyConnection, gid: i32, uid: i32) -> Result<Self, RepoError> {
+        let Some(v) = repo.group_view(conn, gid, uid).await? else {
+            return Ok(GroupView { sort: SORTS[0].to_string(), ..Default::default() });
+        };
+
+        Ok(GroupView {
+            build: v.build,
+            mains: v.mains,
+            member: v.member,
+            hide_obsolete: v.hide_obsolete,
+            min_eligible: v.min_eligible,
+            sort: v.sort,
+            hidden_raids: v.hidden_raids,
+        })
+    }
+
+    pub async fn save(&self, repo: &dyn Repository, conn: &mut AnyConnection, gid: i32, uid: i32) -> Result<(), RepoError> {
+        let record = GroupViewRecord {
+            build: self.build.clone(),
+            mains: self.mains,
+            member: self.member.clone(),
+            hide_obsolete: self.hide_obsolete,
+            min_eligible: self.min_eligible,
+            sort: self.sort.clone(),
+            hidden_raids: self.hidden_raids.clone(),
+        };
+
+        repo.save_group_view(conn, gid, uid, &record).await
+    }
+}
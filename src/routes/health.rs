@@ -0,0 +1,79 @@
+use std::net::IpAddr;
+
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use log::{error, warn};
+use secrecy::ExposeSecret;
+
+use crate::config::MetricsConfig;
+use crate::metrics::Metrics;
+use crate::migrate;
+use crate::proxy::ClientIp;
+use crate::repo::Repository;
+
+/// The process is up and handles requests
+#[get("/healthz")]
+async fn healthz() -> impl Responder {
+    HttpResponse::Ok().body("ok")
+}
+
+/// The database is reachable and has every migration of this binary
+#[get("/readyz")]
+async fn readyz(repo: web::Data<dyn Repository>) -> impl Responder {
+    let status = match migrate::check(repo.as_ref()).await {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Not ready: {}", e);
+            return HttpResponse::ServiceUnavailable().body(e);
+        },
+    };
+
+    match status.iter().filter(|m| m.applied.is_none()).count() {
+        0 => HttpResponse::Ok().body("ok"),
+        pending => HttpResponse::ServiceUnavailable().body(format!("{} migrations are pending", pending)),
+    }
+}
+
+/// Whether the client may read the metrics, see [`MetricsConfig`]
+fn may_scrape(config: &MetricsConfig, client: IpAddr, req: &HttpRequest) -> bool {
+    if config.allow.iter().any(|c| c.contains(client)) {
+        return true;
+    }
+
+    let (Some(token), Some(sent)) = (&config.token, req.headers().get(AUTHORIZATION)) else {
+        return false;
+    };
+
+    match sent.to_str().ok().and_then(|v| v.strip_prefix("Bearer ")) {
+        Some(sent) => constant_time_eq(sent.as_bytes(), token.expose_secret().as_bytes()),
+        None => false,
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Prometheus metrics for the addresses and token in the `[metrics]` config
+#[get("/metrics")]
+async fn show_metrics(
+    req: HttpRequest,
+    client: ClientIp,
+    config: web::Data<MetricsConfig>,
+    repo: web::Data<dyn Repository>,
+    metrics: web::Data<Metrics>,
+) -> impl Responder {
+    if !may_scrape(&config, client.0, &req) {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    match metrics.render(repo.pool()) {
+        Ok(v) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(v),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().body("Could not encode metrics")
+        },
+    }
+}
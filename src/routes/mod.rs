@@ -2,8 +2,16 @@ mod user;
 mod css;
 mod characters;
 mod groups;
+mod roster;
+mod calendar;
+mod admin;
+mod health;
 
 pub use characters::*;
 pub use user::*;
 pub use css::*;
 pub use groups::*;
+pub use roster::*;
+pub use calendar::*;
+pub use admin::*;
+pub use health::*;
@@ -0,0 +1,600 @@
+use std::collections::HashMap;
+
+use actix_session::Session;
+use actix_web::{get, post, web, HttpResponse, Responder, http::header};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use sqlx::AnyConnection;
+use tera::{Tera, Context};
+
+use crate::data::{Character, Class, Raid, GOLD_EARNERS};
+use crate::game_data::GameData;
+use crate::live::{Broadcaster, Change};
+use crate::repo::{RepoError, Repository};
+use crate::rules::Clear;
+use super::characters::{validate_combat_power, validate_item_level, validate_name, validate_texts};
+
+/// A users roster in the format used for exports and imports
+#[derive(Deserialize, Serialize, Debug, Default)]
+struct Roster {
+    characters: Vec<RosterChar>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct RosterChar {
+    name: String,
+    class: String,
+    item_level: i32,
+    #[serde(default = "default_true")]
+    gold_earner: bool,
+    #[serde(default)]
+    build: String,
+    #[serde(default)]
+    stats: String,
+    #[serde(default)]
+    card_set: String,
+    #[serde(default)]
+    combat_power: i32,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    main: bool,
+    /// Raids completed in the current week
+    #[serde(default)]
+    completed: Vec<RosterClear>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct RosterClear {
+    raid: String,
+    difficulty: String,
+    #[serde(default = "default_true")]
+    took_gold: bool,
+}
+
+/// A character as a csv row. Completions are joined with `;` and suffixed with ` (no gold)` for bus runs.
+#[derive(Deserialize, Serialize, Debug)]
+struct CsvChar {
+    name: String,
+    class: String,
+    item_level: i32,
+    gold_earner: bool,
+    build: String,
+    stats: String,
+    card_set: String,
+    combat_power: i32,
+    note: String,
+    main: bool,
+    completed: String,
+}
+
+const NO_GOLD: &str = " (no gold)";
+
+fn default_true() -> bool {
+    true
+}
+
+impl From<RosterChar> for CsvChar {
+    fn from(c: RosterChar) -> Self {
+        let completed: Vec<String> = c.completed.iter()
+            .map(|r| format!("{} {}{}", r.raid, r.difficulty, if r.took_gold { "" } else { NO_GOLD }))
+            .collect();
+
+        CsvChar {
+            name: c.name,
+            class: c.class,
+            item_level: c.item_level,
+            gold_earner: c.gold_earner,
+            build: c.build,
+            stats: c.stats,
+            card_set: c.card_set,
+            combat_power: c.combat_power,
+            note: c.note,
+            main: c.main,
+            completed: completed.join(";"),
+        }
+    }
+}
+
+impl CsvChar {
+    /// Splits the completions into raid name and difficulty using the known raids.
+    /// Unknown raids are kept, split at the last space, so the import reports them on their row.
+    fn into_roster_char(self, raids: &[Raid]) -> RosterChar {
+        let mut completed = Vec::new();
+
+        for clear in self.completed.split(';').map(str::trim).filter(|c| !c.is_empty()) {
+            let (clear, took_gold) = match clear.strip_suffix(NO_GOLD) {
+                Some(c) => (c, false),
+                None => (clear, true),
+            };
+
+            let (raid, difficulty) = match raids.iter().find(|r| format!("{} {}", r.name, r.difficulty).eq_ignore_ascii_case(clear)) {
+                Some(r) => (r.name.clone(), r.difficulty.clone()),
+                None => match clear.rsplit_once(' ') {
+                    Some((r, d)) => (r.to_string(), d.to_string()),
+                    None => (clear.to_string(), String::new()),
+                },
+            };
+
+            completed.push(RosterClear { raid, difficulty, took_gold });
+        }
+
+        RosterChar {
+            name: self.name,
+            class: self.class,
+            item_level: self.item_level,
+            gold_earner: self.gold_earner,
+            build: self.build,
+            stats: self.stats,
+            card_set: self.card_set,
+            combat_power: self.combat_power,
+            note: self.note,
+            main: self.main,
+            completed,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
+}
+
+#[get("/me/export")]
+async fn export_roster(
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    query: web::Query<ExportQuery>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    let data = game_data.get();
+
+    let roster = match load_roster(repo.as_ref(), &mut trans, id, &data.classes, &data.rules.raids).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    match query.format.as_deref() {
+        Some("csv") => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+
+            for c in roster.characters {
+                if let Err(e) = writer.serialize(CsvChar::from(c)) {
+                    error!("{:?}", e);
+                    return HttpResponse::InternalServerError().body("Could not write csv");
+                }
+            }
+
+            let body = match writer.into_inner() {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("{:?}", e);
+                    return HttpResponse::InternalServerError().body("Could not write csv");
+                },
+            };
+
+            HttpResponse::Ok()
+                .content_type("text/csv; charset=utf-8")
+                .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"roster.csv\""))
+                .body(body)
+        },
+        None | Some("json") => {
+            HttpResponse::Ok()
+                .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"roster.json\""))
+                .json(roster)
+        },
+        Some(_) => HttpResponse::BadRequest().body("Unknown export format"),
+    }
+}
+
+async fn load_roster(
+    repo: &dyn Repository,
+    conn: &mut AnyConnection,
+    user_id: i32,
+    classes: &[Class],
+    raids: &[Raid],
+) -> Result<Roster, RepoError> {
+    let chars = repo.characters(&mut *conn, user_id).await?;
+
+    let mut user_clears = repo.clears_of_user(conn, user_id).await?;
+    user_clears.sort_by_key(|c| c.raid_id);
+
+    let mut clears: HashMap<i32, Vec<RosterClear>> = HashMap::new();
+
+    for e in user_clears {
+        let Some(raid) = raids.iter().find(|r| r.id == e.raid_id) else { continue };
+
+        clears.entry(e.character_id).or_default().push(RosterClear {
+            raid: raid.name.clone(),
+            difficulty: raid.difficulty.clone(),
+            took_gold: e.took_gold,
+        });
+    }
+
+    Ok(Roster {
+        characters: chars.into_iter().map(|c| RosterChar {
+            completed: clears.remove(&c.id).unwrap_or_default(),
+            class: classes.iter().find(|cl| cl.id == c.class_id).map(|cl| cl.name.clone()).unwrap_or_default(),
+            name: c.name,
+            item_level: c.item_level,
+            gold_earner: c.gold_earner,
+            build: c.build,
+            stats: c.stats,
+            card_set: c.card_set,
+            combat_power: c.combat_power,
+            note: c.note,
+            main: c.main,
+        }).collect(),
+    })
+}
+
+#[get("/me/import")]
+async fn import_roster(
+    tera: web::Data<Tera>,
+) -> impl Responder {
+    let mut con = Context::new();
+    con.insert("format", "json");
+    con.insert("data", "");
+
+    HttpResponse::Ok().body(tera.render("import.html", &con).unwrap())
+}
+
+#[derive(Deserialize)]
+struct ImportForm {
+    format: String,
+    data: String,
+    /// Only preview the changes when set to `preview`
+    action: String,
+}
+
+/// What importing a character would do, shown as a preview
+#[derive(Serialize, Debug)]
+struct ImportRow {
+    name: String,
+    class: String,
+    item_level: i32,
+    action: &'static str,
+    completed: usize,
+    errors: Vec<String>,
+}
+
+#[post("/me/import")]
+async fn import_roster_post(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    live: web::Data<Broadcaster>,
+    form: web::Form<ImportForm>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+    let form = form.into_inner();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    let data = game_data.get();
+    let classes = &data.classes;
+    let raids = &data.rules.raids;
+
+    let existing: HashMap<String, Character> = match repo.characters_for_update(&mut trans, id).await {
+        Ok(v) => v.into_iter().map(|c| (c.name.to_lowercase(), c)).collect(),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let mut con = Context::new();
+    con.insert("format", &form.format);
+    con.insert("data", &form.data);
+
+    let roster = match parse_roster(&form.format, &form.data, raids) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Invalid roster import: {}", e);
+            con.insert("errors", &vec![e]);
+            return HttpResponse::BadRequest().body(tera.render("import.html", &con).unwrap());
+        },
+    };
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+
+    for c in roster.characters.iter() {
+        let mut row_errors = Vec::new();
+
+        if let Err(e) = validate_name(&c.name) {
+            row_errors.push(e);
+        }
+
+        if let Err(e) = validate_item_level(c.item_level) {
+            row_errors.push(e);
+        }
+
+        if let Err(e) = validate_combat_power(c.combat_power) {
+            row_errors.push(e);
+        }
+
+        if !classes.iter().any(|cl| cl.name.eq_ignore_ascii_case(&c.class)) {
+            row_errors.push(format!("Unknown class {}", c.class));
+        }
+
+        row_errors.extend(validate_texts(&c.build, &c.stats, &c.card_set, &c.note));
+
+        for clear in c.completed.iter() {
+            if find_raid(raids, clear).is_none() {
+                row_errors.push(format!("Unknown raid {} {}", clear.raid, clear.difficulty).trim_end().to_string());
+            }
+        }
+
+        if roster.characters.iter().filter(|o| o.name.eq_ignore_ascii_case(&c.name)).count() > 1 {
+            row_errors.push("Character is listed more than once".to_string());
+        }
+
+        rows.push(ImportRow {
+            name: c.name.clone(),
+            class: c.class.clone(),
+            item_level: c.item_level,
+            action: if existing.contains_key(&c.name.to_lowercase()) { "update" } else { "create" },
+            completed: c.completed.len(),
+            errors: row_errors,
+        });
+    }
+
+    let gold_earners = roster.characters.iter().filter(|c| c.gold_earner).count()
+        + existing.iter()
+            .filter(|(name, c)| c.gold_earner && !roster.characters.iter().any(|r| r.name.to_lowercase() == **name))
+            .count();
+
+    if gold_earners > GOLD_EARNERS {
+        errors.push(format!("Only {} characters can earn gold each week", GOLD_EARNERS));
+    }
+
+    let mains = roster.characters.iter().filter(|c| c.main).count()
+        + existing.iter()
+            .filter(|(name, c)| c.main && !roster.characters.iter().any(|r| r.name.to_lowercase() == **name))
+            .count();
+
+    if mains > 1 {
+        errors.push("Only one character can be your main".to_string());
+    }
+
+    let valid = errors.is_empty() && rows.iter().all(|r| r.errors.is_empty());
+
+    if form.action == "preview" || !valid {
+        con.insert("rows", &rows);
+        con.insert("errors", &errors);
+
+        let mut res = if valid { HttpResponse::Ok() } else { HttpResponse::BadRequest() };
+        return res.body(tera.render("import.html", &con).unwrap());
+    }
+
+    for c in roster.characters.iter() {
+        let class_id = classes.iter().find(|cl| cl.name.eq_ignore_ascii_case(&c.class)).unwrap().id;
+
+        if let Err(e) = import_character(repo.as_ref(), &mut trans, id, c, class_id, existing.get(&c.name.to_lowercase()), raids).await {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Could not import roster");
+        }
+    }
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    live.publish_for_user(repo.as_ref(), Change::Character { user_id: id }).await;
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, "/auth/me/chars")).finish()
+}
+
+fn parse_roster(format: &str, data: &str, raids: &[Raid]) -> Result<Roster, String> {
+    match format {
+        "json" => serde_json::from_str(data).map_err(|e| format!("Invalid json: {}", e)),
+        "csv" => {
+            let mut roster = Roster::default();
+
+            for row in csv::Reader::from_reader(data.as_bytes()).deserialize::<CsvChar>() {
+                let row = row.map_err(|e| format!("Invalid csv: {}", e))?;
+                roster.characters.push(row.into_roster_char(raids));
+            }
+
+            Ok(roster)
+        },
+        f => Err(format!("Unknown format {}", f)),
+    }
+}
+
+fn find_raid<'a>(raids: &'a [Raid], clear: &RosterClear) -> Option<&'a Raid> {
+    raids.iter().find(|r| r.name.eq_ignore_ascii_case(&clear.raid) && r.difficulty.eq_ignore_ascii_case(&clear.difficulty))
+}
+
+/// Creates or updates a single imported character and adds its completions
+async fn import_character(
+    repo: &dyn Repository,
+    conn: &mut AnyConnection,
+    user_id: i32,
+    c: &RosterChar,
+    class_id: i32,
+    existing: Option<&Character>,
+    raids: &[Raid],
+) -> Result<(), RepoError> {
+    let (cid, version) = match existing {
+        Some(e) => {
+            if e.item_level != c.item_level {
+                repo.record_item_level(&mut *conn, e.id, c.item_level).await?;
+            }
+            (e.id, e.version)
+        },
+        None => (repo.add_character_in(&mut *conn, user_id, &c.name, class_id, c.item_level).await?, 0),
+    };
+
+    let chara = Character {
+        id: cid,
+        user_id,
+        name: c.name.clone(),
+        class_id,
+        item_level: c.item_level,
+        gold_earner: c.gold_earner,
+        build: c.build.clone(),
+        stats: c.stats.clone(),
+        card_set: c.card_set.clone(),
+        combat_power: c.combat_power,
+        note: c.note.clone(),
+        main: c.main,
+        sort_order: 0,
+        version,
+    };
+
+    repo.update_character(&mut *conn, &chara, None).await?;
+
+    for clear in c.completed.iter() {
+        let Some(raid) = find_raid(raids, clear) else { continue };
+
+        let clear = Clear {
+            character_id: cid,
+            raid_id: raid.id,
+            took_gold: c.gold_earner && clear.took_gold,
+        };
+
+        repo.merge_clear(&mut *conn, user_id, &clear).await?;
+    }
+
+    Ok(())
+}
+
+#[get("/me/import/armory")]
+async fn import_armory(
+    tera: web::Data<Tera>,
+) -> impl Responder {
+    let mut con = Context::new();
+    con.insert("data", "");
+
+    HttpResponse::Ok().body(tera.render("import_armory.html", &con).unwrap())
+}
+
+#[derive(Deserialize)]
+struct ArmoryForm {
+    data: String,
+    /// Only preview the changes when set to `preview`
+    action: String,
+}
+
+/// A character recognized in the pasted armory text, shown as a preview
+#[derive(Serialize, Debug)]
+struct ArmoryRow {
+    name: String,
+    class: String,
+    item_level: i32,
+    /// The text the class was guessed from if it was not an exact match
+    fuzzy_class: Option<String>,
+    create: bool,
+    errors: Vec<String>,
+}
+
+#[post("/me/import/armory")]
+async fn import_armory_post(
+    tera: web::Data<Tera>,
+    session: Session,
+    repo: web::Data<dyn Repository>,
+    game_data: web::Data<GameData>,
+    live: web::Data<Broadcaster>,
+    form: web::Form<ArmoryForm>,
+) -> impl Responder {
+    let id = session.get::<i32>("id").unwrap().unwrap();
+    let form = form.into_inner();
+
+    let mut trans = match repo.begin().await {
+        Ok(t) => t,
+        Err(_) => panic!("Failed to connect to database"),
+    };
+
+    let data = game_data.get();
+
+    let existing: Vec<String> = match repo.characters_for_update(&mut trans, id).await {
+        Ok(v) => v.into_iter().map(|c| c.name.to_lowercase()).collect(),
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
+    };
+
+    let parsed = crate::armory::parse_roster(&form.data, &data.classes);
+
+    let mut errors = Vec::new();
+    if parsed.characters.is_empty() {
+        errors.push("No characters found in the pasted text".to_string());
+    }
+
+    let rows: Vec<ArmoryRow> = parsed.characters.iter().map(|c| {
+        let mut row_errors = Vec::new();
+
+        if let Err(e) = validate_name(&c.name) {
+            row_errors.push(e);
+        }
+
+        if let Err(e) = validate_item_level(c.item_level) {
+            row_errors.push(e);
+        }
+
+        if parsed.characters.iter().filter(|o| o.name.eq_ignore_ascii_case(&c.name)).count() > 1 {
+            row_errors.push("Character is listed more than once".to_string());
+        }
+
+        ArmoryRow {
+            name: c.name.clone(),
+            class: c.class.clone(),
+            item_level: c.item_level,
+            fuzzy_class: c.fuzzy_class.clone(),
+            create: !existing.contains(&c.name.to_lowercase()),
+            errors: row_errors,
+        }
+    }).collect();
+
+    let valid = errors.is_empty() && rows.iter().all(|r| r.errors.is_empty());
+
+    if form.action == "preview" || !valid {
+        let mut con = Context::new();
+        con.insert("data", &form.data);
+        con.insert("rows", &rows);
+        con.insert("ignored", &parsed.ignored);
+        con.insert("errors", &errors);
+
+        let mut res = if valid { HttpResponse::Ok() } else { HttpResponse::BadRequest() };
+        return res.body(tera.render("import_armory.html", &con).unwrap());
+    }
+
+    for c in parsed.characters.iter().filter(|c| !existing.contains(&c.name.to_lowercase())) {
+        if let Err(e) = repo.add_character_in(&mut trans, id, &c.name, c.class_id, c.item_level).await {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Could not import roster");
+        }
+    }
+
+    match trans.commit().await {
+        Ok(_) => (),
+        Err(_) => return HttpResponse::InternalServerError().body("Database error"),
+    };
+
+    live.publish_for_user(repo.as_ref(), Change::Character { user_id: id }).await;
+
+    HttpResponse::SeeOther().insert_header((header::LOCATION, "/auth/me/chars")).finish()
+}
@@ -1,3 +1,6 @@
+#[cfg(test)]
+mod tests;
+
 use std::ops::Deref;
 
 use actix_session::{Session, SessionExt};
@@ -6,11 +9,14 @@ use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::http::header::{ContentType, LOCATION, self, HeaderValue};
 use actix_web::{get, post, web, HttpResponse, Responder, HttpMessage};
 use actix_web_lab::middleware::Next;
-use sqlx::{MySqlPool, query_as};
-use log::error;
+use log::{error, warn};
 
 use crate::crypto::{argon2_hash_text, argon2_verify_password};
 use crate::data::User;
+use crate::logging;
+use crate::proxy::ClientIp;
+use crate::repo::{RepoError, Repository};
+use crate::throttle::LoginThrottle;
 
 #[get("/register")]
 async fn register_form() -> impl Responder {
@@ -22,16 +28,8 @@ async fn register_form() -> impl Responder {
 #[post("/register")]
 async fn register(
     form: web::Form<User>,
-    pool: web::Data<MySqlPool>,
+    repo: web::Data<dyn Repository>,
 ) -> impl Responder {
-
-    let trans = pool.get_ref().begin().await;
-
-    let mut trans = match trans {
-        Ok(t) => t,
-        Err(e) => panic!("{}", e),
-    };
-
     let hash = argon2_hash_text(&form.password);
 
     let hash = if let Ok(s) = hash {
@@ -41,33 +39,14 @@ async fn register(
         return HttpResponse::BadRequest().body("Invalid password");
     };
 
-    let result = query_as!(
-        User,
-        "INSERT INTO users (username, password_hash) VALUES (?, ?)",
-        form.username,
-        hash,
-    )
-    .execute(&mut trans)
-    .await;
-    match result {
-        Ok(_) => { 
-            if let Err(e) = trans.commit().await{
-                error!("{:?}", e);
-                return HttpResponse::InternalServerError().finish();
-            }
-            return HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/login"))
-                .finish();
-
-        },
+    match repo.create_user(&form.username, &hash).await {
+        Ok(_) => HttpResponse::SeeOther()
+            .insert_header((LOCATION, "/login"))
+            .finish(),
+        Err(RepoError::Duplicate) => HttpResponse::BadRequest().body("User already exists"),
         Err(e) => {
-            return match e {
-                sqlx::Error::Database(_) => HttpResponse::BadRequest().body("User already exists"),
-                _ => {
-                    error!("{:?}", e);
-                    return HttpResponse::InternalServerError().finish();
-                },
-            }
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().finish()
         },
     }
 }
@@ -82,42 +61,45 @@ async fn login_form() -> impl Responder {
 #[post("/login")]
 async fn login(
     form: web::Form<User>,
-    pool: web::Data<MySqlPool>,
+    repo: web::Data<dyn Repository>,
+    throttle: web::Data<LoginThrottle>,
+    ip: ClientIp,
     session: Session,
 ) -> impl Responder {
-    let trans = pool.get_ref().begin().await;
-
-    let mut trans = match trans {
-        Ok(t) => t,
-        Err(e) => panic!("{}", e),
-    };
-
-    let p_hash = sqlx::query!(
-        "SELECT id, password_hash FROM users WHERE username = ?",
-        form.username,
-    )
-    .fetch_one(&mut trans)
-    .await;
+    if throttle.is_blocked(ip.0) {
+        warn!("Too many failed logins from {}", ip.0);
+        return HttpResponse::TooManyRequests().body("Too many failed logins. Please try again later");
+    }
 
-    let (uid, p_hash) = match p_hash {
-        Ok(s) => (s.id, s.password_hash),
-        Err(_) => return HttpResponse::BadRequest().body("Invalid Username or Password"),
+    let (uid, p_hash) = match repo.user_by_name(&form.username).await {
+        Ok(Some(u)) => (u.id, u.password_hash),
+        Ok(None) => {
+            throttle.record_failure(ip.0);
+            return HttpResponse::BadRequest().body("Invalid Username or Password");
+        },
+        Err(e) => {
+            error!("{:?}", e);
+            return HttpResponse::InternalServerError().body("Database error");
+        },
     };
 
     match argon2_verify_password(&form.password, &p_hash){
         Ok(_) => {
-            if let Err(_) = session.insert("id", uid){
+            throttle.clear(ip.0);
+            if let Err(e) = session.insert("id", uid) {
+                error!("{:?}", e);
                 return HttpResponse::InternalServerError().finish();
             }
-            return HttpResponse::SeeOther()
+            HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/auth/me/chars"))
-                .finish();
+                .finish()
         },
         Err(e) => {
             error!("{:?}", e);
-            return HttpResponse::BadRequest().body("Invalid Username or Password");
+            throttle.record_failure(ip.0);
+            HttpResponse::BadRequest().body("Invalid Username or Password")
         }
-    };
+    }
 }
 
 #[get("/logout")]
@@ -125,11 +107,11 @@ async fn logout(
     session: Session,
 ) -> impl Responder {
     session.purge();
-    return HttpResponse::Ok().body("Successfully logged out");
+    HttpResponse::Ok().body("Successfully logged out")
 }
 
 #[derive(Copy, Clone, Debug)]
-pub struct UserId(i32);
+pub struct UserId(pub i32);
 
 impl std::fmt::Display for UserId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -166,6 +148,7 @@ pub async fn reject_unauth_user(
 
     match session.get::<i32>("id").map_err(actix_web::error::ErrorInternalServerError)? {
         Some(uid) => {
+            logging::set_user_id(uid);
             req.extensions_mut().insert(UserId(uid));
             next.call(req).await
         },
@@ -186,4 +169,3 @@ pub async fn add_private_header(
 
     Ok(res)
 }
-
@@ -0,0 +1,113 @@
+//! Handler tests for registration, login and logout, run against the in-process app of [`crate::testing`].
+
+use actix_web::http::header::COOKIE;
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+
+use crate::testing::{TestApp, PASSWORD};
+use crate::throttle::MAX_FAILURES;
+
+#[actix_web::test]
+async fn forms_are_public() {
+    let app = TestApp::spawn().await;
+
+    for uri in ["/register", "/login"] {
+        let res = app.get(uri).await;
+        assert_eq!(res.status, StatusCode::OK, "{}", uri);
+        assert!(res.body.contains("<form"), "{}", uri);
+    }
+}
+
+#[actix_web::test]
+async fn register_creates_user() {
+    let app = TestApp::spawn().await;
+
+    let res = app.register("alice", PASSWORD).await;
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert_eq!(res.location(), Some("/login"));
+
+    let hash: String = sqlx::query_scalar("SELECT password_hash FROM users WHERE username = 'alice'")
+        .fetch_one(app.pool())
+        .await
+        .unwrap();
+    assert!(hash.starts_with("$argon2"), "password is stored hashed");
+}
+
+#[actix_web::test]
+async fn register_rejects_taken_name() {
+    let app = TestApp::spawn().await;
+
+    app.register("alice", PASSWORD).await;
+    let res = app.register("alice", "another password").await;
+
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert_eq!(res.body, "User already exists");
+}
+
+#[actix_web::test]
+async fn login_starts_session() {
+    let app = TestApp::spawn().await;
+
+    app.register("alice", PASSWORD).await;
+    let res = app.login("alice", PASSWORD).await;
+
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert_eq!(res.location(), Some("/auth/me/chars"));
+
+    let cookie = res.cookie(&app.cookie_name).expect("login sets the session cookie");
+    let req = TestRequest::get().uri("/auth/me/chars").insert_header((COOKIE, format!("{}={}", app.cookie_name, cookie)));
+    let res = app.call(req).await;
+
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res.body.contains("alice"));
+}
+
+#[actix_web::test]
+async fn login_rejects_wrong_credentials() {
+    let app = TestApp::spawn().await;
+
+    app.register("alice", PASSWORD).await;
+
+    for (name, password) in [("alice", "wrong password"), ("nobody", PASSWORD)] {
+        let res = app.login(name, password).await;
+        assert_eq!(res.status, StatusCode::BAD_REQUEST, "{}", name);
+        assert_eq!(res.body, "Invalid Username or Password");
+        assert!(res.cookie(&app.cookie_name).is_none());
+    }
+}
+
+#[actix_web::test]
+async fn login_is_throttled_after_failures() {
+    let app = TestApp::spawn().await;
+
+    app.register("alice", PASSWORD).await;
+
+    for _ in 0..MAX_FAILURES {
+        app.login("nobody", PASSWORD).await;
+    }
+
+    // Even the right password is refused until the window has passed
+    let res = app.login("alice", PASSWORD).await;
+    assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[actix_web::test]
+async fn auth_scope_requires_login() {
+    let app = TestApp::spawn().await;
+
+    let res = app.get("/auth/me/chars").await;
+
+    assert_eq!(res.status, StatusCode::SEE_OTHER);
+    assert_eq!(res.location(), Some("/login"));
+}
+
+#[actix_web::test]
+async fn logout_removes_session_cookie() {
+    let app = TestApp::spawn().await;
+
+    let alice = app.user("alice").await;
+    let res = app.get_as(&alice, "/auth/logout").await;
+
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.cookie(&app.cookie_name), Some(""));
+}
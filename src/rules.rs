@@ -0,0 +1,100 @@
+//! Weekly raid eligibility rules.
+//!
+//! The rules are evaluated in memory on data that is loaded once per page, so showing a roster or
+//! a whole group takes the same number of queries no matter how many characters are involved.
+
+use std::collections::HashMap;
+
+use crate::data::Raid;
+
+/// Amount of three-weekly raids that give gold per character
+pub const GOLD_RAIDS: usize = 3;
+
+/// A weekly clear of a raid
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Clear {
+    pub character_id: i32,
+    pub raid_id: i32,
+    pub took_gold: bool,
+}
+
+/// What a character can still do in a raid this week
+#[derive(Debug, Clone)]
+pub struct RaidState {
+    pub raid_id: i32,
+    pub completed: bool,
+    pub took_gold: bool,
+    /// The character may enter the raid, with or without gold
+    pub eligible: bool,
+    /// Entering the raid would pay out gold
+    pub gives_gold: bool,
+    /// The character already took gold from three raids and can only bus this one
+    pub capped: bool,
+}
+
+impl RaidState {
+    /// Whether the raid can still be ticked off on the roster page
+    pub fn available(&self) -> bool {
+        self.eligible && !self.capped
+    }
+}
+
+pub struct RaidRules {
+    pub raids: Vec<Raid>,
+    /// Raids that need at least one of the listed raids to be cleared first
+    prerequisites: HashMap<i32, Vec<i32>>,
+}
+
+impl RaidRules {
+    pub fn new(raids: Vec<Raid>, prerequisites: impl IntoIterator<Item = (i32, i32)>) -> Self {
+        let mut map: HashMap<i32, Vec<i32>> = HashMap::new();
+        for (raid, requires) in prerequisites {
+            map.entry(raid).or_default().push(requires);
+        }
+
+        RaidRules { raids, prerequisites: map }
+    }
+
+    /// The state of every raid, in the order of `raids`, for a character with the given clears
+    pub fn states(&self, item_level: i32, gold_earner: bool, clears: &[&Clear]) -> Vec<RaidState> {
+        let by_id: HashMap<i32, &Raid> = self.raids.iter().map(|r| (r.id, r)).collect();
+
+        let gold_clears = clears.iter()
+            .filter(|c| c.took_gold && by_id.get(&c.raid_id).is_some_and(|r| r.three_weekly == 1))
+            .count();
+
+        self.raids.iter().map(|r| {
+            let clear = clears.iter().find(|c| c.raid_id == r.id);
+
+            // Only one difficulty of a raid can be done each week
+            let same_raid_done = clears.iter()
+                .any(|c| by_id.get(&c.raid_id).is_some_and(|cr| cr.name == r.name));
+
+            let prerequisite_done = match self.prerequisites.get(&r.id) {
+                Some(reqs) => clears.iter().any(|c| reqs.contains(&c.raid_id)),
+                None => true,
+            };
+
+            let eligible = !same_raid_done && prerequisite_done && item_level >= r.required_item_level;
+            let capped = r.three_weekly == 1 && gold_earner && gold_clears >= GOLD_RAIDS;
+
+            RaidState {
+                raid_id: r.id,
+                completed: clear.is_some(),
+                took_gold: clear.is_some_and(|c| c.took_gold),
+                eligible,
+                gives_gold: eligible && gold_earner && !capped,
+                capped,
+            }
+        }).collect()
+    }
+}
+
+/// Groups clears by character
+pub fn clears_by_character(clears: &[Clear]) -> HashMap<i32, Vec<&Clear>> {
+    let mut map: HashMap<i32, Vec<&Clear>> = HashMap::new();
+    for c in clears {
+        map.entry(c.character_id).or_default().push(c);
+    }
+    map
+}
@@ -0,0 +1,107 @@
+//! Supervision of the background jobs that run next to the web server.
+//!
+//! Every job is restarted with a growing delay when it panics or returns early. On shutdown the
+//! jobs are told to stop and are awaited, so a job in the middle of a database transaction can
+//! finish it. Jobs check [`Shutdown`] only between units of work for that reason.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use actix_web::rt::task::JoinHandle;
+use log::{error, info, warn};
+use tokio::sync::watch;
+
+/// First delay before restarting a failed job, doubled on every failure in a row
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// A job that ran this long before failing starts again with the shortest delay
+const HEALTHY_RUN: Duration = Duration::from_secs(10 * 60);
+
+/// Tells a job that the server is shutting down
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Completes once shutdown was requested
+    pub async fn requested(&mut self) {
+        while !*self.0.borrow() {
+            // An error means the supervisor is gone, which is a shutdown as well
+            if self.0.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+pub struct Supervisor {
+    shutdown: watch::Sender<bool>,
+    jobs: Vec<(&'static str, JoinHandle<()>)>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Supervisor { shutdown: watch::channel(false).0, jobs: Vec::new() }
+    }
+}
+
+impl Supervisor {
+    /// Runs the job created by `job` until shutdown, creating a new one whenever it stops early
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, job: F)
+    where
+        F: Fn(Shutdown) -> Fut + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let shutdown = Shutdown(self.shutdown.subscribe());
+
+        let handle = actix_web::rt::spawn(async move {
+            let mut backoff = MIN_BACKOFF;
+
+            loop {
+                let started = Instant::now();
+                let res = actix_web::rt::spawn(job(shutdown.clone())).await;
+
+                if shutdown.is_requested() {
+                    return;
+                }
+
+                if started.elapsed() >= HEALTHY_RUN {
+                    backoff = MIN_BACKOFF;
+                }
+
+                match res {
+                    Ok(()) => warn!("Background job {} stopped, restarting in {:?}", name, backoff),
+                    Err(e) => error!("Background job {} crashed: {}, restarting in {:?}", name, e, backoff),
+                }
+
+                let mut wait = shutdown.clone();
+                actix_web::rt::time::timeout(backoff, wait.requested()).await.ok();
+                if shutdown.is_requested() {
+                    return;
+                }
+
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+
+        self.jobs.push((name, handle));
+    }
+
+    /// Asks every job to stop and waits up to `timeout` for them
+    pub async fn shutdown(self, timeout: Duration) {
+        let _ = self.shutdown.send(true);
+
+        let deadline = Instant::now() + timeout;
+
+        for (name, handle) in self.jobs {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match actix_web::rt::time::timeout(left, handle).await {
+                Ok(_) => info!("Background job {} stopped", name),
+                Err(_) => warn!("Background job {} did not stop in time", name),
+            }
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! An in-process server for handler tests.
+//!
+//! [`TestApp::spawn`] creates a database of its own, applies the migrations and builds the app
+//! with [`crate::app`], the same function `serve` uses. By default that is a SQLite file in the
+//! temporary directory, so the tests run without any setup. With a `mysql://` url in
+//! `TEST_DATABASE_URL` a database is created on that server instead, the user in the url then
+//! needs the right to create and drop databases. Either way the database is removed again at the
+//! end of the test.
+//!
+//! The app runs on the repository like it does in `serve`. Tests check the stored rows through
+//! [`TestApp::pool`], the pool of that repository. Statements are written with `?` placeholders,
+//! which both backends understand.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use actix_web::body::{self, MessageBody};
+use actix_web::dev::Service;
+use actix_web::http::header::{HeaderMap, COOKIE, LOCATION, SET_COOKIE};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web::Data;
+use actix_web::HttpResponse;
+use secrecy::Secret;
+use serde::Serialize;
+use sqlx::any::AnyConnectOptions;
+use sqlx::mysql::MySqlConnectOptions;
+use sqlx::{AnyPool, ConnectOptions, Executor};
+use tera::Tera;
+
+use crate::config::{DatabaseConfig, LogConfig, MetricsConfig, ServerConfig, SessionConfig};
+use crate::crypto::{random_token, CookieSessionSecret};
+use crate::game_data::GameData;
+use crate::live::Broadcaster;
+use crate::metrics::Metrics;
+use crate::proxy::TrustedProxies;
+use crate::repo::{Backend, Repository, SqlRepo};
+use crate::throttle::LoginThrottle;
+use crate::{app, migrate, AppState};
+
+/// Password of every user created by [`TestApp::user`]
+pub const PASSWORD: &str = "correct horse battery staple";
+
+type Call = Box<dyn Fn(TestRequest) -> Pin<Box<dyn Future<Output = TestResponse>>>>;
+
+/// A response with its body read, whether a handler or a middleware produced it
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    async fn read(res: HttpResponse<impl MessageBody>) -> Self {
+        let status = res.status();
+        let headers = res.headers().clone();
+
+        let body = match body::to_bytes(res.into_body()).await {
+            Ok(v) => String::from_utf8_lossy(&v).into_owned(),
+            Err(_) => panic!("Could not read the response body"),
+        };
+
+        TestResponse { status, headers, body }
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.headers.get(LOCATION)?.to_str().ok()
+    }
+
+    /// The raw value of a cookie the response sets
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.headers.get_all(SET_COOKIE)
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| v.split(';').next()?.split_once('='))
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v)
+    }
+}
+
+/// A registered user with the session cookie of their login
+pub struct TestUser {
+    pub id: i32,
+    pub name: String,
+    cookie: String,
+}
+
+pub struct TestApp {
+    call: Call,
+    pub repo: Arc<dyn Repository>,
+    pub cookie_name: String,
+    // Dropped last, after the app let go of its connections
+    _database: TestDatabase,
+}
+
+impl TestApp {
+    /// Builds the app on a fresh database, see the module documentation for which one
+    pub async fn spawn() -> TestApp {
+        dotenv::dotenv().ok();
+
+        let database = match std::env::var("TEST_DATABASE_URL") {
+            Ok(url) => TestDatabase::mysql(&url).await,
+            Err(_) => TestDatabase::sqlite(),
+        };
+
+        let repo: Arc<dyn Repository> = Arc::new(database.connect().await);
+        migrate::up(repo.as_ref()).await.expect("Could not apply migrations");
+        let game_data = GameData::load(repo.as_ref()).await.expect("Could not load game data");
+
+        let server = ServerConfig::default();
+        let session = SessionConfig::default();
+        let cookie_name = session.cookie_name.clone();
+
+        let state = AppState {
+            repo: repo.clone(),
+            tera: Tera::new(&server.templates).expect("Could not load templates"),
+            game_data: Data::new(game_data),
+            live: Data::new(Broadcaster::default()),
+            proxies: Data::new(TrustedProxies::default()),
+            throttle: Data::new(LoginThrottle::default()),
+            metrics: Data::new(Metrics::new(DatabaseConfig::default().max_connections).expect("Could not register metrics")),
+            metrics_access: Data::new(MetricsConfig::default()),
+            cookie_secret: CookieSessionSecret { secret: Secret::new(random_token()) },
+            session,
+            secure_cookies: false,
+            access_log: LogConfig::default().access_log,
+            static_dir: server.static_dir,
+        };
+
+        let service = Rc::new(test::init_service(app(&state)).await);
+
+        let call: Call = Box::new(move |req: TestRequest| -> Pin<Box<dyn Future<Output = TestResponse>>> {
+            let service = service.clone();
+            Box::pin(async move {
+                // The server turns errors of middleware, like the login check, into responses as well
+                match service.call(req.to_request()).await {
+                    Ok(res) => TestResponse::read(res.into_parts().1).await,
+                    Err(e) => TestResponse::read(e.error_response()).await,
+                }
+            })
+        });
+
+        TestApp { call, repo, cookie_name, _database: database }
+    }
+
+    /// The pool of the repository, to check stored rows
+    pub fn pool(&self) -> &AnyPool {
+        self.repo.pool()
+    }
+
+    pub async fn call(&self, req: TestRequest) -> TestResponse {
+        (self.call)(req).await
+    }
+
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        self.call(TestRequest::get().uri(uri)).await
+    }
+
+    pub async fn post(&self, uri: &str, form: &impl Serialize) -> TestResponse {
+        self.call(TestRequest::post().uri(uri).set_form(form)).await
+    }
+
+    pub async fn get_as(&self, user: &TestUser, uri: &str) -> TestResponse {
+        self.call(TestRequest::get().uri(uri).insert_header((COOKIE, user.cookie.clone()))).await
+    }
+
+    pub async fn post_as(&self, user: &TestUser, uri: &str, form: &impl Serialize) -> TestResponse {
+        self.call(TestRequest::post().uri(uri).insert_header((COOKIE, user.cookie.clone())).set_form(form)).await
+    }
+
+    pub async fn register(&self, name: &str, password: &str) -> TestResponse {
+        self.post("/register", &[("username", name), ("password", password)]).await
+    }
+
+    pub async fn login(&self, name: &str, password: &str) -> TestResponse {
+        self.post("/login", &[("username", name), ("password", password)]).await
+    }
+
+    /// Registers a user with [`PASSWORD`] and logs them in
+    pub async fn user(&self, name: &str) -> TestUser {
+        let res = self.register(name, PASSWORD).await;
+        assert_eq!(res.status, StatusCode::SEE_OTHER, "register {}: {}", name, res.body);
+
+        let res = self.login(name, PASSWORD).await;
+        assert_eq!(res.status, StatusCode::SEE_OTHER, "login {}: {}", name, res.body);
+
+        let cookie = res.cookie(&self.cookie_name).expect("login sets the session cookie");
+
+        TestUser {
+            id: self.user_id(name).await.expect("registered user exists"),
+            name: name.to_string(),
+            cookie: format!("{}={}", self.cookie_name, cookie),
+        }
+    }
+
+    pub async fn user_id(&self, name: &str) -> Option<i32> {
+        sqlx::query_scalar("SELECT id FROM users WHERE username = ?")
+            .bind(name)
+            .fetch_optional(self.pool())
+            .await
+            .unwrap()
+    }
+
+    /// Adds a character through the form of the roster page and returns its id
+    pub async fn add_character(&self, user: &TestUser, name: &str, item_level: i32) -> i32 {
+        let class_id: i32 = sqlx::query_scalar("SELECT id FROM classes ORDER BY id LIMIT 1")
+            .fetch_one(self.pool())
+            .await
+            .unwrap();
+
+        let form = [
+            ("id", "0".to_string()),
+            ("user_id", user.id.to_string()),
+            ("name", name.to_string()),
+            ("class_id", class_id.to_string()),
+            ("item_level", item_level.to_string()),
+        ];
+        let res = self.post_as(user, "/auth/me/add_char", &form).await;
+        assert_eq!(res.status, StatusCode::SEE_OTHER, "add character {}: {}", name, res.body);
+
+        sqlx::query_scalar("SELECT id FROM characters WHERE user_id = ? AND name = ?")
+            .bind(user.id)
+            .bind(name)
+            .fetch_one(self.pool())
+            .await
+            .unwrap()
+    }
+
+    /// Creates a group owned by `user` and returns its id
+    pub async fn create_group(&self, user: &TestUser, name: &str) -> i32 {
+        let res = self.post_as(user, "/auth/me/groups/new", &[("name", name)]).await;
+        assert_eq!(res.status, StatusCode::SEE_OTHER, "create group {}: {}", name, res.body);
+
+        sqlx::query_scalar("SELECT id FROM groups WHERE creator_id = ? AND name = ?")
+            .bind(user.id)
+            .bind(name)
+            .fetch_one(self.pool())
+            .await
+            .unwrap()
+    }
+
+    /// Invites `user` into a group of `owner` and returns the id of the invite
+    pub async fn invite(&self, owner: &TestUser, gid: i32, user: &TestUser) -> i32 {
+        let res = self.post_as(owner, &format!("/auth/me/groups/edit/{}/invite", gid), &[("name", &user.name)]).await;
+        assert_eq!(res.status, StatusCode::SEE_OTHER, "invite {}: {}", user.name, res.body);
+
+        sqlx::query_scalar("SELECT id FROM invites WHERE group_id = ? AND dest = ?")
+            .bind(gid)
+            .bind(user.id)
+            .fetch_one(self.pool())
+            .await
+            .unwrap()
+    }
+
+    /// Invites `user` into a group of `owner` and accepts the invite
+    pub async fn join(&self, owner: &TestUser, gid: i32, user: &TestUser) {
+        let iid = self.invite(owner, gid, user).await;
+
+        let res = self.get_as(user, &format!("/auth/me/invites/accept/{}", iid)).await;
+        assert_eq!(res.status, StatusCode::SEE_OTHER, "accept invite {}: {}", iid, res.body);
+    }
+
+    pub async fn is_member(&self, gid: i32, uid: i32) -> bool {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM group_members WHERE group_id = ? AND user_id = ?")
+            .bind(gid)
+            .bind(uid)
+            .fetch_one(self.pool())
+            .await
+            .unwrap() > 0
+    }
+}
+
+/// A database of its own for one test, removed when the test ends
+enum TestDatabase {
+    Sqlite(PathBuf),
+    MySql {
+        server: Box<MySqlConnectOptions>,
+        name: String,
+    },
+}
+
+impl TestDatabase {
+    fn sqlite() -> Self {
+        TestDatabase::Sqlite(std::env::temp_dir().join(format!("la_test_{}.db", &random_token()[..16])))
+    }
+
+    async fn mysql(url: &str) -> Self {
+        let server = MySqlConnectOptions::from_str(url).expect("TEST_DATABASE_URL must be a mysql:// url");
+        let name = format!("la_test_{}", &random_token()[..16]);
+
+        let mut conn = server.connect().await.expect("Failed to connect to TEST_DATABASE_URL");
+        conn.execute(format!("CREATE DATABASE `{}`", name).as_str())
+            .await
+            .expect("Could not create the test database");
+
+        TestDatabase::MySql { server: Box::new(server), name }
+    }
+
+    async fn connect(&self) -> SqlRepo {
+        let res = match self {
+            TestDatabase::Sqlite(path) => {
+                let url = format!("sqlite://{}", path.display());
+                SqlRepo::connect(&DatabaseConfig::default(), &url, Backend::Sqlite).await
+            },
+            TestDatabase::MySql { server, name } => {
+                let options = AnyConnectOptions::from(server.clone().database(name));
+                AnyPool::connect_with(options).await.map(|pool| SqlRepo::new(pool, Backend::MySql))
+            },
+        };
+
+        res.expect("Failed to connect to the test database")
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        let (server, name) = match self {
+            TestDatabase::Sqlite(path) => {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    eprintln!("Could not remove the test database {}: {}", path.display(), e);
+                }
+                return;
+            },
+            TestDatabase::MySql { server, name } => (*server.clone(), name.clone()),
+        };
+        let sql = format!("DROP DATABASE `{}`", name);
+
+        // Drop can't await, so the statement runs on a runtime of its own. That also works while
+        // the test is unwinding from a failed assertion.
+        let res = std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let mut conn = server.connect().await?;
+                conn.execute(sql.as_str()).await
+            })
+        }).join();
+
+        if !matches!(res, Ok(Ok(_))) {
+            eprintln!("Could not drop the test database {}", name);
+        }
+    }
+}
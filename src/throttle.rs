@@ -0,0 +1,41 @@
+//! Limits failed logins per client address to slow down password guessing.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Failed logins a client may have within `WINDOW` before further attempts are refused
+pub(crate) const MAX_FAILURES: usize = 10;
+const WINDOW: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Default)]
+pub struct LoginThrottle {
+    failures: Mutex<HashMap<IpAddr, Vec<Instant>>>,
+}
+
+impl LoginThrottle {
+    pub fn is_blocked(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        self.failures.lock().unwrap()
+            .get(&ip)
+            .is_some_and(|f| f.iter().filter(|t| now - **t < WINDOW).count() >= MAX_FAILURES)
+    }
+
+    pub fn record_failure(&self, ip: IpAddr) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+
+        // Forget clients that stopped trying, so the map does not grow forever
+        failures.retain(|_, f| {
+            f.retain(|t| now - *t < WINDOW);
+            !f.is_empty()
+        });
+
+        failures.entry(ip).or_default().push(now);
+    }
+
+    pub fn clear(&self, ip: IpAddr) {
+        self.failures.lock().unwrap().remove(&ip);
+    }
+}
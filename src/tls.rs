@@ -0,0 +1,135 @@
+//! Certificates that can be replaced while the server is running.
+//!
+//! Every new connection picks up the current certificate during the handshake, so a renewed
+//! certificate is used without a restart and without dropping open connections. The files are
+//! checked for changes periodically and reloaded on SIGHUP.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use log::{error, info, warn};
+use openssl::error::ErrorStack;
+use openssl::ssl::{ClientHelloResponse, SslAcceptor, SslAcceptorBuilder, SslContext, SslFiletype, SslMethod};
+
+use crate::config::TlsConfig;
+use crate::tasks::Shutdown;
+
+pub struct CertStore {
+    key: PathBuf,
+    cert: PathBuf,
+    current: RwLock<SslContext>,
+    /// Modification times of the files that were loaded last
+    loaded: Mutex<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+fn acceptor_builder(key: &Path, cert: &Path) -> Result<SslAcceptorBuilder, ErrorStack> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+
+    builder.set_private_key_file(key, SslFiletype::PEM)?;
+
+    builder.set_certificate_chain_file(cert)?;
+
+    builder.check_private_key()?;
+
+    Ok(builder)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl CertStore {
+    pub fn load(config: &TlsConfig) -> Result<Arc<Self>, ErrorStack> {
+        let mtimes = (modified(&config.key), modified(&config.cert));
+        let context = acceptor_builder(&config.key, &config.cert)?.build().into_context();
+
+        Ok(Arc::new(CertStore {
+            key: config.key.clone(),
+            cert: config.cert.clone(),
+            current: RwLock::new(context),
+            loaded: Mutex::new(mtimes),
+        }))
+    }
+
+    /// An acceptor for one listener that switches every handshake to the current certificate.
+    /// The switch happens in the client hello callback, which unlike the servername callback
+    /// also runs for clients that don't send SNI.
+    pub fn acceptor(self: &Arc<Self>) -> Result<SslAcceptorBuilder, ErrorStack> {
+        let mut builder = acceptor_builder(&self.key, &self.cert)?;
+
+        let store = self.clone();
+        builder.set_client_hello_callback(move |ssl, _| {
+            let context = store.current.read().unwrap().clone();
+            if let Err(e) = ssl.set_ssl_context(&context) {
+                error!("Could not use the current certificate: {}", e);
+            }
+            Ok(ClientHelloResponse::SUCCESS)
+        });
+
+        Ok(builder)
+    }
+
+    /// Reads the key and certificate again. On errors the previous certificate stays in use.
+    pub fn reload(&self) -> Result<(), ErrorStack> {
+        let mtimes = (modified(&self.key), modified(&self.cert));
+        let context = acceptor_builder(&self.key, &self.cert)?.build().into_context();
+
+        *self.current.write().unwrap() = context;
+        *self.loaded.lock().unwrap() = mtimes;
+
+        info!("Loaded certificate {}", self.cert.display());
+
+        Ok(())
+    }
+
+    fn changed(&self) -> bool {
+        *self.loaded.lock().unwrap() != (modified(&self.key), modified(&self.cert))
+    }
+
+    /// Reloads the certificate whenever the files change
+    pub async fn watch(self: Arc<Self>, interval: Duration, mut shutdown: Shutdown) {
+        let mut interval = actix_web::rt::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => (),
+                _ = shutdown.requested() => return,
+            }
+
+            // A renewal may replace the certificate before the key, so failures are retried on
+            // the next check until both files fit together
+            if self.changed() {
+                if let Err(e) = self.reload() {
+                    warn!("Could not reload the certificate yet: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Reloads the certificate on SIGHUP
+    #[cfg(unix)]
+    pub async fn reload_on_hangup(self: Arc<Self>, mut shutdown: Shutdown) {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Could not listen for SIGHUP: {}", e);
+                return;
+            },
+        };
+
+        loop {
+            tokio::select! {
+                Some(_) = hangup.recv() => (),
+                _ = shutdown.requested() => return,
+                else => return,
+            }
+
+            if let Err(e) = self.reload() {
+                error!("Could not reload the certificate: {}", e);
+            }
+        }
+    }
+}
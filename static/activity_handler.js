@@ -1,17 +1,32 @@
 // Assuming the server returns an array of updated activities
 // with their id, completed status, and available status
-async function updateActivityOnServer(characterId, activityId, completed) {
+async function updateActivityOnServer(characterId, activityId, completed, tookGold) {
     const formData = new FormData();
     formData.append("character_id", characterId);
     formData.append("activity_id", activityId);
     formData.append("completed", completed);
+    formData.append("took_gold", tookGold);
 
     const data = new URLSearchParams();
     for (const pair of formData) {
         data.append(pair[0], pair[1]);
     }
   
-    const response = await fetch("update_activity", {
+    return postUpdate("update_activity", data);
+  }
+
+// Daily and weekly tasks report their rest bonus instead of availability
+async function updateTaskOnServer(characterId, taskId, completed) {
+    const data = new URLSearchParams();
+    data.append("character_id", characterId);
+    data.append("task_id", taskId);
+    data.append("completed", completed);
+
+    return postUpdate("update_task", data);
+  }
+
+  async function postUpdate(url, data) {
+    const response = await fetch(url, {
       method: "POST",
       body: data,
     });
@@ -23,11 +38,14 @@ async function updateActivityOnServer(characterId, activityId, completed) {
     return response.json(); // Return the updated activities
   }
   
-  function updateActivityState(activityBox, completed, available) {
-    activityBox.classList.remove("completed", "not-completed", "unavailable");
+  function updateActivityState(activityBox, completed, tookGold, available) {
+    activityBox.classList.remove("completed", "no-gold", "not-completed", "unavailable");
   
     if (completed) {
       activityBox.classList.add("completed");
+      if (!tookGold) {
+        activityBox.classList.add("no-gold");
+      }
     } else if (available) {
       activityBox.classList.add("not-completed");
     } else {
@@ -35,8 +53,16 @@ async function updateActivityOnServer(characterId, activityId, completed) {
     }
   }
   
+  function updateTaskState(taskBox, completed, restBonus) {
+    taskBox.classList.remove("completed", "not-completed");
+    taskBox.classList.add(completed ? "completed" : "not-completed");
+    taskBox.title = `Rest bonus: ${restBonus}`;
+    taskBox.textContent = restBonus > 0 ? restBonus : "";
+  }
+
   async function toggleActivity(event) {
     const activityBox = event.target;
+    const kind = activityBox.dataset.kind;
     const characterId = activityBox.dataset.characterId;
     const activityId = activityBox.dataset.activityId;
     const currentState = activityBox.classList.contains("completed") ? "completed" : activityBox.classList.contains("not-completed") ? "not-completed" : "unavailable";
@@ -49,12 +75,23 @@ async function updateActivityOnServer(characterId, activityId, completed) {
     const nextState = currentState === "completed" ? "not-completed" : "completed";
   
     try {
-      const updatedActivities = await updateActivityOnServer(characterId, activityId, nextState === "completed");
+      if (kind === "task") {
+        const updatedTasks = await updateTaskOnServer(characterId, activityId, nextState === "completed");
+
+        updatedTasks.forEach(({ id, completed, rest_bonus }) => {
+          const updatedTaskBox = document.querySelector(`.activity-box[data-kind="task"][data-character-id="${characterId}"][data-activity-id="${id}"]`);
+          updateTaskState(updatedTaskBox, completed, rest_bonus);
+        });
+        return;
+      }
+
+      // Shift-click records a bus/helper run that does not take gold
+      const updatedActivities = await updateActivityOnServer(characterId, activityId, nextState === "completed", !event.shiftKey);
   
       // Update the activity states in the UI
-      updatedActivities.forEach(({ id, completed, available }) => {
-        const updatedActivityBox = document.querySelector(`.activity-box[data-character-id="${characterId}"][data-activity-id="${id}"]`);
-        updateActivityState(updatedActivityBox, completed, available);
+      updatedActivities.forEach(({ id, completed, took_gold, available }) => {
+        const updatedActivityBox = document.querySelector(`.activity-box[data-kind="raid"][data-character-id="${characterId}"][data-activity-id="${id}"]`);
+        updateActivityState(updatedActivityBox, completed, took_gold, available);
       });
     } catch (error) {
       console.error("Error updating activity:", error);
@@ -0,0 +1,26 @@
+// Reloads the raid matrix whenever someone in the group ticks a raid,
+// edits a character or joins or leaves the group
+const groupUrl = window.location.pathname;
+let reloadPending = false;
+
+async function reloadMatrix() {
+    reloadPending = false;
+
+    const response = await fetch(groupUrl + "/matrix");
+    if (!response.ok) {
+        console.error(`Failed to reload the group: ${response.statusText}`);
+        return;
+    }
+
+    document.getElementById("matrix").innerHTML = await response.text();
+}
+
+const source = new EventSource(groupUrl + "/live");
+
+source.addEventListener("change", () => {
+    // A burst of changes only needs one reload
+    if (!reloadPending) {
+        reloadPending = true;
+        setTimeout(reloadMatrix, 500);
+    }
+});
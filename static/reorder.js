@@ -0,0 +1,31 @@
+// Lets the user drag characters on the edit page into a new order.
+// The order of the rows in the form is saved as the sort order.
+let draggedRow = null;
+
+function dragStart(event) {
+  draggedRow = event.currentTarget;
+  event.dataTransfer.effectAllowed = "move";
+}
+
+function dragOver(event) {
+  event.preventDefault();
+  const target = event.currentTarget;
+
+  if (draggedRow === null || target === draggedRow) {
+    return;
+  }
+
+  const rect = target.getBoundingClientRect();
+  const after = event.clientY > rect.top + rect.height / 2;
+  target.parentNode.insertBefore(draggedRow, after ? target.nextSibling : target);
+}
+
+function dragEnd() {
+  draggedRow = null;
+}
+
+document.querySelectorAll(".char-edit").forEach((row) => {
+  row.addEventListener("dragstart", dragStart);
+  row.addEventListener("dragover", dragOver);
+  row.addEventListener("dragend", dragEnd);
+});